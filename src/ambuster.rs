@@ -1,31 +1,90 @@
 extern crate regex;
-use crate::expression::Expr;
 use crate::expression::Scope;
-use crate::lexer::Cursor;
 
 use regex::Regex;
 
 impl Scope {
-    fn has_evil_amp(&mut self) -> bool {
-        self.selectors.iter().find(|s| has_evil_amp(s)).is_some()
+    pub fn has_evil_amp(&self, policy: &Policy) -> bool {
+        self.selectors.iter().any(|s| policy.is_evil(s))
     }
 }
 
 lazy_static! {
-    static ref RE: Regex = Regex::new(r"([a-zA-Z0-9_\-]&)|(&[a-zA-Z0-9_\-])").unwrap();
+    // `&` with the characters concatenated to it, e.g. `&__title` or `a-&`
+    static ref RE: Regex = Regex::new(r"[a-zA-Z0-9_\-]*&[a-zA-Z0-9_\-]*").unwrap();
 }
 
-fn has_evil_amp(s: &str) -> bool {
-    match RE.captures(s) {
-        Some(_) => true,
-        None => false,
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Pattern, String> {
+        let regex =
+            Regex::new(source).map_err(|e| format!("invalid pattern `{}`: {}", source, e))?;
+        Ok(Pattern {
+            source: source.to_string(),
+            regex,
+        })
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.source == other.source
+    }
+}
+
+// Why a selector is evil.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Trigger {
+    Concatenated,   // any concatenated ampersand not allowed
+    Denied(String), // the deny pattern it matches
+}
+
+// Patterns deciding which concatenated ampersands are evil. They are
+// searched in the ampersand with its concatenated characters, such as
+// `&--active` of `.a &--active:hover`. Deny patterns win over allow ones.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Policy {
+    pub allow: Vec<Pattern>,
+    pub deny: Vec<Pattern>,
+}
+
+impl Policy {
+    pub fn is_evil(&self, selector: &str) -> bool {
+        self.trigger(selector).is_some()
+    }
+
+    pub fn trigger(&self, selector: &str) -> Option<Trigger> {
+        RE.find_iter(selector)
+            .map(|m| m.as_str())
+            .filter(|amp| amp.len() > 1)
+            .find_map(|amp| self.judge(amp))
+    }
+
+    fn judge(&self, amp: &str) -> Option<Trigger> {
+        if let Some(p) = self.deny.iter().find(|p| p.regex.is_match(amp)) {
+            return Some(Trigger::Denied(p.source.clone()));
+        }
+        if self.allow.iter().any(|p| p.regex.is_match(amp)) {
+            return None;
+        }
+        Some(Trigger::Concatenated)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::bool_assert_comparison)]
 mod ambuster {
     use super::*;
 
+    fn has_evil_amp(s: &str) -> bool {
+        Policy::default().is_evil(s)
+    }
+
     #[test]
     fn test_has_evil_amp_false() {
         assert_eq!(has_evil_amp(".a"), false);
@@ -49,4 +108,27 @@ mod ambuster {
         assert_eq!(has_evil_amp("a-&"), true);
         assert_eq!(has_evil_amp("a_&"), true);
     }
+
+    #[test]
+    fn policy() {
+        let patterns = |v: &[&str]| v.iter().map(|s| Pattern::new(s).unwrap()).collect();
+        let policy = Policy {
+            allow: patterns(&["^&--", "^&-(enter|leave)(-active)?$"]),
+            deny: patterns(&["^&__", "^&--disabled$"]),
+        };
+        assert_eq!(policy.trigger("&--active:hover"), None);
+        assert_eq!(policy.trigger("&-enter-active"), None);
+        assert_eq!(policy.trigger(".a &:hover"), None);
+        assert_eq!(
+            policy.trigger("&__title"),
+            Some(Trigger::Denied("^&__".to_string()))
+        );
+        assert_eq!(
+            policy.trigger("&--disabled"),
+            Some(Trigger::Denied("^&--disabled$".to_string()))
+        );
+        assert_eq!(policy.trigger("&-title"), Some(Trigger::Concatenated));
+        assert_eq!(policy.trigger("&--a, a-&"), Some(Trigger::Concatenated));
+        assert!(Pattern::new("(").is_err());
+    }
 }
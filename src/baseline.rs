@@ -0,0 +1,234 @@
+use crate::checker::Finding;
+use crate::git;
+use crate::report::Record;
+
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+const VERSION: u32 = 1;
+
+lazy_static! {
+    // paths in baselines are relative to the top level of the working tree,
+    // or to the current directory outside of one
+    static ref ROOT: PathBuf = {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let root = git::toplevel(&cwd).unwrap_or(cwd);
+        fs::canonicalize(&root).unwrap_or(root)
+    };
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub file: String,
+    pub rule: String,
+    pub selector: String,
+    pub resolved: Vec<String>,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub version: u32,
+    pub entries: Vec<Entry>,
+}
+
+impl Baseline {
+    pub fn new(records: &[Record]) -> Baseline {
+        Baseline {
+            version: VERSION,
+            entries: records
+                .iter()
+                .map(|r| Entry {
+                    file: normalize(&r.file),
+                    rule: r.rule.clone(),
+                    selector: r.selector.clone(),
+                    resolved: r.resolved.clone(),
+                    fingerprint: r.fingerprint.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn read(content: &str) -> Result<Baseline, String> {
+        let baseline: Baseline = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        if baseline.version != VERSION {
+            return Err(format!("unsupported baseline version {}", baseline.version));
+        }
+        Ok(baseline)
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap() + "\n"
+    }
+
+    // Splits the records into the ones missing from the baseline, and the
+    // entries of the checked files which no longer occur. Each entry
+    // matches one record, so a copied rule counts as new.
+    pub fn compare(&self, records: Vec<Record>, files: &[String]) -> (Vec<Record>, Vec<Entry>) {
+        let mut remaining: HashMap<&str, Vec<&Entry>> = HashMap::new();
+        for entry in self.entries.iter().rev() {
+            remaining
+                .entry(entry.fingerprint.as_str())
+                .or_default()
+                .push(entry);
+        }
+
+        let new = records
+            .into_iter()
+            .filter(|r| {
+                remaining
+                    .get_mut(r.fingerprint.as_str())
+                    .and_then(Vec::pop)
+                    .is_none()
+            })
+            .collect();
+
+        let files: HashSet<String> = files.iter().map(|f| normalize(f)).collect();
+        let stale = self
+            .entries
+            .iter()
+            .filter(|e| {
+                remaining
+                    .get(e.fingerprint.as_str())
+                    .is_some_and(|v| v.iter().any(|r| std::ptr::eq(*r, *e)))
+            })
+            .filter(|e| files.contains(&e.file.replace('\\', "/")))
+            .cloned()
+            .collect();
+        (new, stale)
+    }
+}
+
+// Identifies a finding by its content rather than its position, so that
+// it survives edits elsewhere in the file and runs from other directories.
+// The surrounding text is the whole lines of the finding without
+// indentation.
+pub fn fingerprint(file: &str, input: &str, finding: &Finding) -> String {
+    let text: Vec<&str> = input
+        .lines()
+        .skip(finding.range.from.row)
+        .take(finding.range.to.row - finding.range.from.row + 1)
+        .map(str::trim)
+        .collect();
+
+    let mut hasher = Sha256::new();
+    for part in &[
+        normalize(file).as_str(),
+        finding.rule,
+        &finding.selector,
+        &finding.resolved.join(", "),
+        &text.join("\n"),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// The path of the file relative to the root, with `/` separators.
+fn normalize(file: &str) -> String {
+    let path = Path::new(file);
+    let path = fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+    let relative = path.strip_prefix(&*ROOT).unwrap_or(&path);
+    relative
+        .to_string_lossy()
+        .replace('\\', "/")
+        .trim_start_matches("./")
+        .to_string()
+}
+
+#[cfg(test)]
+mod compare {
+    use super::*;
+    use crate::checker;
+    use crate::config::Severity;
+
+    fn records(file: &str, input: &str) -> Vec<Record> {
+        checker::check(input, &Default::default())
+            .iter()
+            .map(|f| Record::new(file, input, f, Severity::Warning))
+            .collect()
+    }
+
+    fn selectors(records: &[Record]) -> Vec<&str> {
+        records.iter().map(|r| r.selector.as_str()).collect()
+    }
+
+    #[test]
+    fn fingerprint_ignores_position() {
+        let before = records("a.scss", ".a {\n  &-b {\n    color: red;\n  }\n}\n");
+        let after = records(
+            "./a.scss",
+            "// moved\n\n.a {\n  // rta: .a-b\n  &-b {\n    color: red;\n  }\n}\n",
+        );
+        assert_eq!(before[0].fingerprint, after[0].fingerprint);
+        assert_eq!(before[0].fingerprint.len(), 32);
+
+        let changed = records("a.scss", ".a {\n  .x, &-b {\n    color: red;\n  }\n}\n");
+        assert_ne!(before[0].fingerprint, changed[0].fingerprint);
+        let other = records("b.scss", ".a {\n  &-b {\n    color: red;\n  }\n}\n");
+        assert_ne!(before[0].fingerprint, other[0].fingerprint);
+    }
+
+    #[test]
+    fn paths_from_root() {
+        let cwd = std::env::current_dir().unwrap();
+        let root = git::toplevel(&cwd).unwrap();
+        let relative = cwd.strip_prefix(&root).unwrap().join("src/a.scss");
+        let absolute = cwd.join("src/a.scss");
+        assert_eq!(normalize("src/a.scss"), relative.to_string_lossy());
+        assert_eq!(
+            normalize(&absolute.to_string_lossy()),
+            normalize("./src/a.scss")
+        );
+
+        let input = ".a {\n  &-b {}\n}\n";
+        let absolute = records(&absolute.to_string_lossy(), input);
+        assert_eq!(
+            absolute[0].fingerprint,
+            records("src/a.scss", input)[0].fingerprint
+        );
+        let baseline = Baseline::new(&absolute);
+        assert_eq!(baseline.entries[0].file, relative.to_string_lossy());
+    }
+
+    #[test]
+    fn new_and_stale() {
+        let baseline = Baseline::new(&records("a.scss", ".a {\n  &-b {}\n  &-c {}\n}\n"));
+        let baseline = Baseline::read(&baseline.json()).unwrap();
+
+        let current = records("a.scss", ".a {\n  &-b {}\n  &-d {}\n  &-b {}\n}\n");
+        let files = vec!["a.scss".to_string()];
+        let (new, stale) = baseline.compare(current, &files);
+        assert_eq!(selectors(&new), vec!["&-d", "&-b"]);
+        assert_eq!(
+            stale
+                .iter()
+                .map(|e| e.selector.as_str())
+                .collect::<Vec<_>>(),
+            vec!["&-c"]
+        );
+
+        let (_, stale) = baseline.compare(vec![], &["b.scss".to_string()]);
+        assert_eq!(stale, vec![]);
+    }
+
+    #[test]
+    fn version() {
+        assert!(Baseline::read("{\"version\": 2, \"entries\": []}").is_err());
+        assert!(Baseline::read("[]").is_err());
+    }
+}
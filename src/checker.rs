@@ -0,0 +1,227 @@
+use crate::ambuster::Policy;
+use crate::ambuster::Trigger;
+use crate::fixer;
+use crate::lexer::Cursor;
+use crate::lexer::Lexer;
+use crate::lexer::PToken;
+use crate::lexer::Range;
+use crate::lexer::Token;
+use crate::parser::Parser;
+use crate::resolver;
+use crate::suppress;
+
+pub const EVIL_AMPERSAND: &str = "evil-ampersand";
+pub const UNUSED_SUPPRESSION: &str = "unused-suppression";
+
+pub struct Rule {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: EVIL_AMPERSAND,
+        name: "EvilAmpersand",
+        description: "Selector concatenated with `&` cannot be found by the class names it builds",
+    },
+    Rule {
+        id: UNUSED_SUPPRESSION,
+        name: "UnusedSuppression",
+        description: "Suppression comment which does not disable any finding",
+    },
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub range: Range,
+    pub scope: Range, // the whole rule holding the selector
+    pub selector: String,
+    pub resolved: Vec<String>,
+    pub fixable: bool,
+    pub policy: Option<String>, // deny pattern which made the selector evil
+}
+
+impl Finding {
+    pub fn message(&self) -> String {
+        match self.rule {
+            UNUSED_SUPPRESSION => format!("unused suppression `{}`", self.selector),
+            _ => {
+                let mut message = format!(
+                    "evil ampersand `{}` builds `{}`",
+                    self.selector,
+                    self.resolved.join(", ")
+                );
+                if let Some(pattern) = &self.policy {
+                    message.push_str(&format!(" (denied by `{}`)", pattern));
+                }
+                message
+            }
+        }
+    }
+}
+
+// Finds every selector built with an evil ampersand, except the ones
+// disabled by suppression comments.
+pub fn check(input: &str, policy: &Policy) -> Vec<Finding> {
+    let chars: Vec<char> = input.chars().collect();
+    let (exprs, _) = Parser::new(Lexer::new(chars.clone())).parse();
+    let tokens = tokens(chars);
+
+    let refusals = fixer::refusals(&exprs, policy);
+    let mut findings = vec![];
+    for resolved in resolver::resolve(&exprs) {
+        let scope = resolved.scope;
+        if !scope.has_evil_amp(policy) {
+            continue;
+        }
+
+        let fixable = !refusals.iter().any(|r| r.range == scope.range);
+
+        let ranges = selector_ranges(&tokens, &scope.range.from);
+        for (i, selector) in scope.selectors.iter().enumerate() {
+            let trigger = match policy.trigger(selector) {
+                Some(trigger) => trigger,
+                None => continue,
+            };
+            findings.push(Finding {
+                rule: EVIL_AMPERSAND,
+                range: ranges
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| scope.range.clone()),
+                scope: scope.range.clone(),
+                selector: selector.clone(),
+                resolved: resolver::resolve_selectors(
+                    &resolved.parents,
+                    std::slice::from_ref(selector),
+                ),
+                fixable,
+                policy: match trigger {
+                    Trigger::Denied(pattern) => Some(pattern),
+                    Trigger::Concatenated => None,
+                },
+            });
+        }
+    }
+    suppress::apply(&exprs, findings)
+}
+
+pub fn tokens(chars: Vec<char>) -> Vec<PToken> {
+    let mut lexer = Lexer::new(chars);
+    let mut tokens = vec![];
+    while let Some(pt) = lexer.token() {
+        tokens.push(pt);
+    }
+    tokens
+}
+
+// Ranges of the comma separated selectors of the scope starting at `from`.
+pub fn selector_ranges(tokens: &[PToken], from: &Cursor) -> Vec<Range> {
+    let start = tokens.partition_point(|pt| &pt.range.from < from);
+
+    let mut ranges = vec![];
+    let mut curr: Option<Range> = None;
+    for pt in &tokens[start..] {
+        match pt.token {
+            Token::Comment(_) => (),
+            Token::Comma | Token::LBrace => {
+                if let Some(range) = curr.take() {
+                    ranges.push(range);
+                }
+                if pt.token == Token::LBrace {
+                    break;
+                }
+            }
+            _ => match curr.as_mut() {
+                Some(range) => range.to = pt.range.to.clone(),
+                None => curr = Some(pt.range.clone()),
+            },
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod check {
+    use super::*;
+
+    #[test]
+    fn position() {
+        assert_eq!(
+            check(
+                ".card {\n  &__title,\n  &:hover, &-a:focus {}\n}",
+                &Policy::default()
+            ),
+            vec![
+                Finding {
+                    rule: EVIL_AMPERSAND,
+                    range: Range::new(Cursor::new(1, 2), Cursor::new(1, 9)),
+                    scope: Range::new(Cursor::new(1, 2), Cursor::new(2, 22)),
+                    selector: "&__title".to_string(),
+                    resolved: vec![".card__title".to_string()],
+                    fixable: true,
+                    policy: None,
+                },
+                Finding {
+                    rule: EVIL_AMPERSAND,
+                    range: Range::new(Cursor::new(2, 11), Cursor::new(2, 19)),
+                    scope: Range::new(Cursor::new(1, 2), Cursor::new(2, 22)),
+                    selector: "&-a:focus".to_string(),
+                    resolved: vec![".card-a:focus".to_string()],
+                    fixable: true,
+                    policy: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn message() {
+        let findings = check(".a, .b { .c &-d {} }", &Policy::default());
+        assert_eq!(
+            findings[0].message(),
+            "evil ampersand `.c &-d` builds `.c .a-d, .c .b-d`"
+        );
+        assert_eq!(findings[0].range.from, Cursor::new(0, 9));
+        assert!(!findings[0].fixable);
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(
+            check(".a { &:hover {} & .b {} }", &Policy::default()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn policy() {
+        let policy = Policy {
+            allow: vec![crate::ambuster::Pattern::new("^&--").unwrap()],
+            deny: vec![crate::ambuster::Pattern::new("^&__").unwrap()],
+        };
+        let findings = check(".a {\n  &--b {}\n  &__c {}\n  &-d {}\n}", &policy);
+        assert_eq!(
+            findings.iter().map(|f| f.message()).collect::<Vec<_>>(),
+            vec![
+                "evil ampersand `&__c` builds `.a__c` (denied by `^&__`)",
+                "evil ampersand `&-d` builds `.a-d`",
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let input = ".a {\n  color red;\n  &-b {}\n}\n}\n{ .x {} }\n.c { &-d {} }\n";
+        let findings = check(input, &Policy::default());
+        assert_eq!(
+            findings.iter().map(|f| f.message()).collect::<Vec<_>>(),
+            vec![
+                "evil ampersand `&-b` builds `.a-b`",
+                "evil ampersand `&-d` builds `.c-d`",
+            ]
+        );
+    }
+}
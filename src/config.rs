@@ -0,0 +1,315 @@
+use crate::ambuster;
+use crate::marker;
+use crate::walker;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub const FILE_NAME: &str = ".sass-rta.toml";
+
+const FORMATS: &[&str] = &["text", "json", "sarif"];
+
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Off,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    root: bool, // stops looking for configs in parent directories
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    format: Option<String>,
+    marker: MarkerFile,
+    ampersand: AmpersandFile,
+    rules: BTreeMap<String, Severity>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MarkerFile {
+    prefix: Option<String>,
+    format: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AmpersandFile {
+    allow: Option<Vec<String>>,
+    deny: Option<Vec<String>>,
+}
+
+// Settings merged from the config files of a directory and its parents,
+// where the nearest file wins.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Config {
+    pub include: Option<walker::Patterns>,
+    pub exclude: Option<walker::Patterns>,
+    pub format: Option<String>,
+    pub marker: marker::Style,
+    pub ampersand: ambuster::Policy,
+    pub rules: BTreeMap<String, Severity>,
+}
+
+impl Config {
+    pub fn severity(&self, rule: &str) -> Severity {
+        *self.rules.get(rule).unwrap_or(&Severity::Warning)
+    }
+
+    pub fn walker_options(&self) -> walker::Options {
+        let mut options = walker::Options::default();
+        if let Some(include) = &self.include {
+            options.include = include.clone();
+        }
+        if let Some(exclude) = &self.exclude {
+            options.exclude = exclude.clone();
+        }
+        options
+    }
+
+    fn merge(mut self, dir: &Path, file: File) -> Result<Config, String> {
+        if let Some(globs) = file.include {
+            self.include = Some(walker::Patterns::new(Some(dir), globs));
+        }
+        if let Some(globs) = file.exclude {
+            self.exclude = Some(walker::Patterns::new(Some(dir), globs));
+        }
+        if file.format.is_some() {
+            self.format = file.format;
+        }
+        if let Some(prefix) = file.marker.prefix {
+            self.marker.prefix = prefix;
+        }
+        if let Some(format) = file.marker.format {
+            self.marker.format = format;
+        }
+        // the prefix and the format may come from different files
+        if self.marker.prefix.starts_with("/*") && !self.marker.format.trim_end().ends_with("*/") {
+            return Err(format!(
+                "{}: marker format must end with `*/` for a `/*` prefix",
+                dir.join(FILE_NAME).display()
+            ));
+        }
+        if let Some(allow) = file.ampersand.allow {
+            self.ampersand.allow = patterns(dir, &allow)?;
+        }
+        if let Some(deny) = file.ampersand.deny {
+            self.ampersand.deny = patterns(dir, &deny)?;
+        }
+        self.rules.extend(file.rules);
+        Ok(self)
+    }
+}
+
+// Loads configs once per directory. Clones share the loaded configs.
+#[derive(Default, Clone)]
+pub struct Loader {
+    cache: Arc<Mutex<HashMap<PathBuf, Result<Config, String>>>>,
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    // Config applying to the file or directory at `path`.
+    pub fn load(&self, path: &Path) -> Result<Config, String> {
+        let path = std::path::absolute(path).map_err(|e| e.to_string())?;
+        let dir = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(&path)
+        };
+        self.load_dir(dir)
+    }
+
+    fn load_dir(&self, dir: &Path) -> Result<Config, String> {
+        if let Some(config) = self.cache.lock().unwrap().get(dir) {
+            return config.clone();
+        }
+
+        let config = match read(dir) {
+            Ok(Some(file)) if file.root => Config::default().merge(dir, file),
+            Ok(Some(file)) => self.parent(dir).and_then(|c| c.merge(dir, file)),
+            Ok(None) => self.parent(dir),
+            Err(e) => Err(e),
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), config.clone());
+        config
+    }
+
+    fn parent(&self, dir: &Path) -> Result<Config, String> {
+        match dir.parent() {
+            Some(parent) => self.load_dir(parent),
+            None => Ok(Config::default()),
+        }
+    }
+}
+
+fn patterns(dir: &Path, sources: &[String]) -> Result<Vec<ambuster::Pattern>, String> {
+    sources
+        .iter()
+        .map(|s| ambuster::Pattern::new(s))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("{}: {}", dir.join(FILE_NAME).display(), e))
+}
+
+fn read(dir: &Path) -> Result<Option<File>, String> {
+    let path = dir.join(FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let error = |e: String| format!("{}: {}", path.display(), e);
+    let content = fs::read_to_string(&path).map_err(|e| error(e.to_string()))?;
+    let file: File = toml::from_str(&content).map_err(|e| error(e.to_string()))?;
+    validate(&file).map_err(error)?;
+    Ok(Some(file))
+}
+
+fn validate(file: &File) -> Result<(), String> {
+    if let Some(format) = &file.format {
+        if !FORMATS.contains(&format.as_str()) {
+            return Err(format!("unknown format `{}`", format));
+        }
+    }
+    if let Some(prefix) = &file.marker.prefix {
+        if !prefix.starts_with("//") && !prefix.starts_with("/*") {
+            return Err("marker prefix must start a comment".to_string());
+        }
+    }
+    if let Some(format) = &file.marker.format {
+        if !format.starts_with("{prefix}") {
+            return Err("marker format must start with `{prefix}`".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod load {
+    use super::*;
+
+    fn setup(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn hierarchy() {
+        let dir = setup(&[
+            (
+                FILE_NAME,
+                "root = true\nformat = \"json\"\n[marker]\nprefix = \"// @see\"\n[rules]\nevil-ampersand = \"error\"\n",
+            ),
+            (
+                "pkg/.sass-rta.toml",
+                "exclude = [\"legacy\"]\n[ampersand]\nallow = [\"^&--\"]\n[rules]\nunused-suppression = \"off\"\n",
+            ),
+            ("pkg/a/b.scss", ""),
+        ]);
+        let loader = Loader::new();
+        let config = loader.load(&dir.path().join("pkg/a/b.scss")).unwrap();
+        assert_eq!(config.format, Some("json".to_string()));
+        assert_eq!(config.marker.prefix, "// @see");
+        assert_eq!(config.marker.format, marker::FORMAT);
+        assert_eq!(config.severity("evil-ampersand"), Severity::Error);
+        assert_eq!(config.severity("unused-suppression"), Severity::Off);
+        assert_eq!(
+            config.exclude,
+            Some(walker::Patterns::new(
+                Some(&dir.path().join("pkg")),
+                vec!["legacy".to_string()]
+            ))
+        );
+        assert_eq!(config.include, None);
+        assert_eq!(
+            config.ampersand.allow,
+            vec![ambuster::Pattern::new("^&--").unwrap()]
+        );
+        assert_eq!(config.ampersand.deny, vec![]);
+
+        let config = loader.load(dir.path()).unwrap();
+        assert_eq!(config.severity("unused-suppression"), Severity::Warning);
+        assert_eq!(config.exclude, None);
+    }
+
+    #[test]
+    fn root() {
+        let dir = setup(&[
+            (FILE_NAME, "format = \"json\"\n"),
+            ("pkg/.sass-rta.toml", "root = true\n"),
+        ]);
+        let config = Loader::new().load(&dir.path().join("pkg")).unwrap();
+        assert_eq!(config.format, None);
+    }
+
+    #[test]
+    fn block_comment_marker() {
+        let dir = setup(&[
+            (
+                FILE_NAME,
+                "[marker]\nformat = \"{prefix} {selectors} */\"\n",
+            ),
+            ("pkg/.sass-rta.toml", "[marker]\nprefix = \"/* rta:\"\n"),
+            (
+                "other/.sass-rta.toml",
+                "[marker]\nformat = \"{prefix} {selectors}\"\n",
+            ),
+        ]);
+        let loader = Loader::new();
+        let config = loader.load(&dir.path().join("pkg")).unwrap();
+        assert_eq!(
+            config.marker.render(&[".a-b".to_string()]),
+            "/* rta: .a-b */"
+        );
+        assert!(loader.load(&dir.path().join("other")).is_ok());
+        let dir = setup(&[
+            (
+                FILE_NAME,
+                "[marker]\nprefix = \"/* rta:\"\nformat = \"{prefix} {selectors} */\"\n",
+            ),
+            (
+                "pkg/.sass-rta.toml",
+                "[marker]\nformat = \"{prefix} {selectors}\"\n",
+            ),
+        ]);
+        assert!(Loader::new().load(&dir.path().join("pkg")).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        for content in &[
+            "format = \"xml\"\n",
+            "unknown = 1\n",
+            "[marker]\nprefix = \"rta:\"\n",
+            "[marker]\nformat = \"{selectors}\"\n",
+            "[marker]\nprefix = \"/* rta:\"\n",
+            "[rules]\nevil-ampersand = \"fatal\"\n",
+            "[ampersand]\ndeny = [\"(\"]\n",
+        ] {
+            let dir = setup(&[(FILE_NAME, content)]);
+            assert!(Loader::new().load(dir.path()).is_err(), "{}", content);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::lexer::Range;
+
+use similar::TextDiff;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Renders the change of a file as a unified diff with file headers.
+pub fn unified(path: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(path, path)
+        .to_string()
+}
+
+// Rows added or modified on the new side of a unified diff, by file.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Changes {
+    files: HashMap<PathBuf, Vec<(usize, usize)>>, // 0-based, inclusive
+}
+
+impl Changes {
+    // Reads diffs as made by `git diff` or `diff -u`, whose paths are
+    // relative to `root`. Removed files and pure deletions add no rows.
+    pub fn parse(diff: &str, root: &Path) -> Result<Changes, String> {
+        let mut changes = Changes::default();
+        let mut file: Option<PathBuf> = None;
+        let mut row = 0;
+        let mut left = (0, 0); // lines of the hunk left on each side
+        for (i, line) in diff.lines().enumerate() {
+            if left == (0, 0) {
+                if let Some(path) = line.strip_prefix("+++ ") {
+                    let path = path.split('\t').next().unwrap_or("").trim();
+                    file = match path {
+                        "/dev/null" => None,
+                        _ => Some(resolve(&root.join(path.strip_prefix("b/").unwrap_or(path)))),
+                    };
+                } else if line.starts_with("@@") {
+                    let (start, lengths) = hunk_header(line)
+                        .ok_or_else(|| format!("line {}: invalid hunk header", i + 1))?;
+                    row = start;
+                    left = lengths;
+                }
+                continue;
+            }
+
+            match line.chars().next() {
+                Some('+') => {
+                    if let Some(file) = &file {
+                        let rows = changes.files.entry(file.clone()).or_default();
+                        match rows.last_mut() {
+                            Some((_, to)) if *to + 1 == row => *to = row,
+                            _ => rows.push((row, row)),
+                        }
+                    }
+                    row += 1;
+                    left.1 = left.1.saturating_sub(1);
+                }
+                Some('-') => left.0 = left.0.saturating_sub(1),
+                Some('\\') => (), // no newline at end of file
+                _ => {
+                    row += 1;
+                    left = (left.0.saturating_sub(1), left.1.saturating_sub(1));
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    // Whether the range of `file` has an added or modified row.
+    pub fn touches(&self, file: &str, range: &Range) -> bool {
+        self.files
+            .get(&resolve(Path::new(file)))
+            .into_iter()
+            .flatten()
+            .any(|(from, to)| *from <= range.to.row && range.from.row <= *to)
+    }
+}
+
+// Start row of the new side and the lengths of both sides of a
+// `@@ -1,3 +1,4 @@` header. Lengths default to 1.
+fn hunk_header(line: &str) -> Option<(usize, (usize, usize))> {
+    let mut words = line.split_whitespace().skip(1);
+    let old = range(words.next()?.strip_prefix('-')?)?;
+    let new = range(words.next()?.strip_prefix('+')?)?;
+    Some((new.0.saturating_sub(1), (old.1, new.1)))
+}
+
+fn range(text: &str) -> Option<(usize, usize)> {
+    let mut parts = text.split(',');
+    let start = parts.next()?.parse().ok()?;
+    let length = match parts.next() {
+        Some(length) => length.parse().ok()?,
+        None => 1,
+    };
+    Some((start, length))
+}
+
+// Absolute path of the file, with symbolic links resolved when it exists.
+fn resolve(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod unified {
+    use super::*;
+
+    #[test]
+    fn changed() {
+        assert_eq!(
+            unified(
+                "a.scss",
+                ".a {\n  &-b {}\n}\n",
+                ".a {\n  // rta: .a-b\n  &-b {}\n}\n"
+            ),
+            "--- a.scss\n+++ a.scss\n@@ -1,3 +1,4 @@\n .a {\n+  // rta: .a-b\n   &-b {}\n }\n"
+        );
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(unified("a.scss", ".a {}\n", ".a {}\n"), "");
+    }
+}
+
+#[cfg(test)]
+mod changes {
+    use super::*;
+    use crate::lexer::Cursor;
+
+    fn rows(from: usize, to: usize) -> Range {
+        Range::new(Cursor::new(from, 0), Cursor::new(to, 0))
+    }
+
+    #[test]
+    fn parse() {
+        let diff = "diff --git a/src/a.scss b/src/a.scss
+index 1111111..2222222 100644
+--- a/src/a.scss
++++ b/src/a.scss
+@@ -1,4 +1,5 @@
+ .a {
+-  &-b {}
++  &-c {}
++  &-d {}
+   .e {}
+ }
+@@ -10,2 +11,3 @@ .f {
+ .g {
++  &-h {}
+ }
+diff --git a/old.scss b/old.scss
+deleted file mode 100644
+--- a/old.scss
++++ /dev/null
+@@ -1 +0,0 @@
+-.a {}
+";
+        let changes = Changes::parse(diff, Path::new("/repo")).unwrap();
+        assert_eq!(
+            changes.files,
+            vec![(PathBuf::from("/repo/src/a.scss"), vec![(1, 2), (11, 11)])]
+                .into_iter()
+                .collect()
+        );
+        assert!(changes.touches("/repo/src/a.scss", &rows(0, 1)));
+        assert!(changes.touches("/repo/./src/a.scss", &rows(11, 11)));
+        assert!(!changes.touches("/repo/src/a.scss", &rows(3, 10)));
+        assert!(!changes.touches("/repo/packages/x/src/a.scss", &rows(0, 20)));
+        assert!(!changes.touches("/repo/old.scss", &rows(0, 0)));
+    }
+
+    #[test]
+    fn headers_only_outside_hunks() {
+        let diff = "--- a/a.scss
++++ b/a.scss
+@@ -1,3 +1,4 @@
+ .a {}
+-- b/c.scss
++++ b/d.scss
++@@ -1 +1 @@
+ .e {}
+@@ -9 +11 @@
+-.f {}
++.g {}
+";
+        let changes = Changes::parse(diff, Path::new("/repo")).unwrap();
+        assert_eq!(
+            changes.files,
+            vec![(PathBuf::from("/repo/a.scss"), vec![(1, 2), (10, 10)])]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn plain_diff() {
+        let cwd = std::env::current_dir().unwrap();
+        let diff = unified("a.scss", ".a {}\n", ".a {}\n.b {}\n");
+        let changes = Changes::parse(&diff, &cwd).unwrap();
+        assert!(changes.touches("a.scss", &rows(1, 1)));
+        assert!(!changes.touches("a.scss", &rows(0, 0)));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Changes::parse("+++ b/a.scss\n@@ broken @@\n", Path::new("/repo")).is_err());
+    }
+}
@@ -1,9 +1,16 @@
 use crate::lexer::Range;
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub range: Range,
+    pub value: String,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Scope {
     pub range: Range,
     pub selectors: Vec<String>,
+    pub comments: Vec<Comment>, // comments right before the scope
     pub children: Vec<Expr>,
 }
 
@@ -12,32 +19,64 @@ pub struct Property {
     pub range: Range,
     pub key: String,
     pub value: String,
+    pub comments: Vec<Comment>, // comments right before the property
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Import {
     pub range: Range,
-    pub path: String,
+    pub paths: Vec<ImportPath>,
+    pub comments: Vec<Comment>, // comments right before the import
 }
 
+// One of the comma-separated urls of an `@import`.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Include {
+pub struct ImportPath {
+    pub range: Range,          // url as written, with its quotes or `url()`
+    pub path: String,          // url without its quotes or `url()`
+    pub media: Option<String>, // media queries following the url
+    pub css: bool,             // plain CSS import left to the browser
+}
+
+// A part of a rule with where it is written, such as the url of a `@use`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Word {
     pub range: Range,
-    todo: String,
+    pub value: String,
 }
 
+// `$name: value` in the `with (...)` configuration of a module.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Mixin {
+pub struct Setting {
     pub range: Range,
-    todo: String,
-    pub children: Vec<Expr>,
+    pub name: Word,
+    pub value: Word,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Media {
+pub struct Use {
+    pub range: Range,
+    pub url: Word,               // without its quotes
+    pub namespace: Option<Word>, // name given with `as`, `*` for none
+    pub with: Vec<Setting>,
+    pub comments: Vec<Comment>, // comments right before the rule
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Forward {
+    pub range: Range,
+    pub url: Word,            // without its quotes
+    pub prefix: Option<Word>, // prefix given with `as`, without its `*`
+    pub show: Vec<Word>,
+    pub hide: Vec<Word>,
+    pub with: Vec<Setting>,
+    pub comments: Vec<Comment>, // comments right before the rule
+}
+
+// Input skipped by the parser up to where it could resume.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error {
     pub range: Range,
-    pub name: String,
-    pub children: Vec<Expr>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -45,7 +84,8 @@ pub enum Expr {
     Scope(Scope),
     Property(Property),
     Import(Import),
-    Include(Include),
-    Mixin(Mixin),
-    Media(Media),
+    Use(Use),
+    Forward(Forward),
+    Comment(Comment), // comment not followed by anything in its block
+    Error(Error),
 }
@@ -0,0 +1,208 @@
+use crate::checker;
+use crate::expression::Expr;
+use crate::expression::Scope;
+use crate::lexer::Lexer;
+use crate::lexer::PToken;
+use crate::lexer::Range;
+use crate::parser::Parser;
+use crate::resolver;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Location {
+    pub range: Range,
+    pub selector: String,
+    pub resolved: String,
+}
+
+// A selector resolved on one of its parents, empty for root rules.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Built<'a> {
+    pub scope: &'a Scope,
+    pub range: Range,
+    pub selector: &'a str,
+    pub parent: String,
+    pub resolved: String,
+}
+
+// Resolves every selector of every rule separately, with the range where
+// it is written.
+pub fn built<'a>(exprs: &'a [Expr], tokens: &[PToken]) -> Vec<Built<'a>> {
+    let mut vec = vec![];
+    for resolved in resolver::resolve(exprs) {
+        let scope = resolved.scope;
+        let ranges = checker::selector_ranges(tokens, &scope.range.from);
+        let parents = if resolved.parents.is_empty() {
+            vec!["".to_string()]
+        } else {
+            resolved.parents
+        };
+        for (i, selector) in scope.selectors.iter().enumerate() {
+            for parent in &parents {
+                let built = if parent.is_empty() {
+                    selector.clone()
+                } else {
+                    resolver::resolve_selectors(
+                        std::slice::from_ref(parent),
+                        std::slice::from_ref(selector),
+                    )
+                    .remove(0)
+                };
+                vec.push(Built {
+                    scope,
+                    range: ranges
+                        .get(i)
+                        .cloned()
+                        .unwrap_or_else(|| scope.range.clone()),
+                    selector,
+                    parent: parent.clone(),
+                    resolved: built,
+                });
+            }
+        }
+    }
+    vec
+}
+
+// Finds the selectors whose resolution adds the class or id of `query`,
+// such as `.card__title--active` built by `&--active` in `&__title`. Rules
+// only inheriting it from their parents are left out. A query without `.`
+// or `#` is taken as a class.
+pub fn find(input: &str, query: &str) -> Vec<Location> {
+    let query = normalize(query);
+    let chars: Vec<char> = input.chars().collect();
+    let (exprs, _) = Parser::new(Lexer::new(chars.clone())).parse();
+    let tokens = checker::tokens(chars);
+
+    built(&exprs, &tokens)
+        .into_iter()
+        .filter(|b| builds(&b.resolved, &b.parent, &query))
+        .map(|b| Location {
+            range: b.range,
+            selector: b.selector.to_string(),
+            resolved: b.resolved,
+        })
+        .collect()
+}
+
+pub fn normalize(query: &str) -> String {
+    let query = query.trim();
+    if query.starts_with('.') || query.starts_with('#') {
+        query.to_string()
+    } else {
+        format!(".{}", query)
+    }
+}
+
+// Whether the resolved selector adds the class or id of the normalized
+// query to its parent.
+pub fn builds(resolved: &str, parent: &str, query: &str) -> bool {
+    count(resolved, query) > count(parent, query)
+}
+
+// Classes and ids which the resolved selector adds to its parent, the
+// queries `builds` is true for.
+pub fn added(resolved: &str, parent: &str) -> Vec<String> {
+    let mut vec: Vec<String> = names(resolved)
+        .into_iter()
+        .filter(|n| builds(resolved, parent, n))
+        .map(str::to_string)
+        .collect();
+    vec.sort();
+    vec.dedup();
+    vec
+}
+
+fn count(selector: &str, query: &str) -> usize {
+    names(selector).iter().filter(|n| **n == query).count()
+}
+
+// Every `.` or `#` of the selector followed by name characters, ending
+// wherever the name cannot go on, so `.md\:flex` gives `.md` and
+// `.md\:flex`.
+fn names(selector: &str) -> Vec<&str> {
+    let is_name = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    let mut vec = vec![];
+    for (start, c) in selector.char_indices() {
+        if c != '.' && c != '#' {
+            continue;
+        }
+        let mut end = start + 1;
+        loop {
+            let next = selector[end..].chars().next();
+            match next {
+                Some(c) if is_name(c) => end += c.len_utf8(),
+                _ => {
+                    if end > start + 1 {
+                        vec.push(&selector[start..end]);
+                    }
+                    match (next, selector[end..].chars().nth(1)) {
+                        (Some('\\'), Some(c)) => end += 1 + c.len_utf8(),
+                        _ => break,
+                    }
+                }
+            }
+        }
+    }
+    vec
+}
+
+#[cfg(test)]
+mod find {
+    use super::*;
+
+    fn found(input: &str, query: &str) -> Vec<(usize, String, String)> {
+        find(input, query)
+            .into_iter()
+            .map(|l| (l.range.from.row, l.selector, l.resolved))
+            .collect()
+    }
+
+    #[test]
+    fn concatenated() {
+        let input = ".card {\n  &__title {\n    &--active:hover {}\n    .icon {}\n  }\n}\n";
+        assert_eq!(
+            found(input, ".card__title--active"),
+            vec![(
+                2,
+                "&--active:hover".to_string(),
+                ".card__title--active:hover".to_string()
+            )]
+        );
+        assert_eq!(
+            found(input, "card__title"),
+            vec![(1, "&__title".to_string(), ".card__title".to_string())]
+        );
+        assert_eq!(
+            found(input, ".card"),
+            vec![(0, ".card".to_string(), ".card".to_string())]
+        );
+        assert_eq!(found(input, ".card__tit"), vec![]);
+    }
+
+    #[test]
+    fn multiple_parents() {
+        let input = ".a, .b {\n  @media print {\n    &-c, .d {}\n  }\n}\n";
+        assert_eq!(
+            found(input, ".b-c"),
+            vec![(2, "&-c".to_string(), ".b-c".to_string())]
+        );
+        assert_eq!(
+            found(input, "d"),
+            vec![
+                (2, ".d".to_string(), ".a .d".to_string()),
+                (2, ".d".to_string(), ".b .d".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn id() {
+        assert_eq!(
+            found("#main { &-nav {} }", "#main-nav"),
+            vec![(0, "&-nav".to_string(), "#main-nav".to_string())]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use crate::ambuster::Policy;
+use crate::checker::EVIL_AMPERSAND;
+use crate::expression::Expr;
+use crate::expression::Scope;
+use crate::lexer::Cursor;
+use crate::lexer::Lexer;
+use crate::lexer::Range;
+use crate::lexer::Token;
+use crate::marker;
+use crate::parser;
+use crate::parser::ParseError;
+use crate::parser::Parser;
+use crate::resolver;
+use crate::suppress::Directives;
+
+use std::collections::BTreeSet;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Reason {
+    NoParent,
+    MultipleParentSelectors,
+    AtRule(String),
+    ParentNotFixed,
+    Suppressed,
+}
+
+impl Reason {
+    pub fn message(&self) -> String {
+        match self {
+            Reason::NoParent => "no parent rule to resolve `&`".to_string(),
+            Reason::MultipleParentSelectors => "parent rule has multiple selectors".to_string(),
+            Reason::AtRule(name) => format!("cannot move out of `{}` block", name),
+            Reason::ParentNotFixed => "parent rule cannot be fixed".to_string(),
+            Reason::Suppressed => "disabled by a suppression comment".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Refusal {
+    pub range: Range,
+    pub selectors: Vec<String>,
+    pub reason: Reason,
+}
+
+enum Parent<'a> {
+    Root,
+    Rule(&'a Scope),
+    AtRule(String),
+}
+
+// Un-nests every rule built with an evil ampersand into a sibling of its
+// parent rule, one level per pass, until nothing more can be moved.
+// Returns the fixed input and the rules which had to be left as they are,
+// or the parse errors of the input.
+pub fn fix(
+    input: &str,
+    style: &marker::Style,
+    policy: &Policy,
+) -> Result<(String, Vec<Refusal>), Vec<ParseError>> {
+    let refusals = refusals(&parser::parse(input)?, policy);
+
+    let mut output = input.to_string();
+    loop {
+        let (exprs, _) = Parser::new(Lexer::new(output.chars().collect())).parse();
+        let directives = Directives::new(&exprs);
+        let mut targets = vec![];
+        collect(&exprs, &Parent::Root, policy, &directives, &mut targets);
+
+        if targets.is_empty() {
+            return Ok((output, refusals));
+        }
+        output = hoist(&output, &targets, style);
+    }
+}
+
+// Finds the rules built with an evil ampersand which `fix` cannot move.
+pub fn refusals(exprs: &[Expr], policy: &Policy) -> Vec<Refusal> {
+    let directives = Directives::new(exprs);
+    let mut vec = vec![];
+    walk(exprs, &Parent::Root, true, policy, &directives, &mut vec);
+    vec
+}
+
+fn walk(
+    exprs: &[Expr],
+    parent: &Parent,
+    parent_fixable: bool,
+    policy: &Policy,
+    directives: &Directives,
+    refusals: &mut Vec<Refusal>,
+) {
+    for expr in exprs {
+        match expr {
+            Expr::Scope(s) if s.is_at_rule() => {
+                walk(
+                    &s.children,
+                    &at_rule(s),
+                    parent_fixable,
+                    policy,
+                    directives,
+                    refusals,
+                );
+            }
+            Expr::Scope(s) => {
+                let mut fixable = true;
+                if s.has_evil_amp(policy) {
+                    let reason = match parent {
+                        _ if directives.disable(EVIL_AMPERSAND, &s.range.from) => {
+                            Some(Reason::Suppressed)
+                        }
+                        Parent::Root => Some(Reason::NoParent),
+                        Parent::AtRule(name) => Some(Reason::AtRule(name.clone())),
+                        Parent::Rule(p) if p.selectors.len() > 1 => {
+                            Some(Reason::MultipleParentSelectors)
+                        }
+                        Parent::Rule(_) if !parent_fixable => Some(Reason::ParentNotFixed),
+                        Parent::Rule(_) => None,
+                    };
+                    if let Some(reason) = reason {
+                        fixable = false;
+                        refusals.push(Refusal {
+                            range: s.range.clone(),
+                            selectors: s.selectors.clone(),
+                            reason,
+                        });
+                    }
+                }
+                walk(
+                    &s.children,
+                    &Parent::Rule(s),
+                    fixable,
+                    policy,
+                    directives,
+                    refusals,
+                );
+            }
+            _ => (),
+        }
+    }
+}
+
+fn collect<'a>(
+    exprs: &'a [Expr],
+    parent: &Parent<'a>,
+    policy: &Policy,
+    directives: &Directives,
+    targets: &mut Vec<(&'a Scope, &'a Scope)>,
+) {
+    for expr in exprs {
+        match expr {
+            Expr::Scope(s) if s.is_at_rule() => {
+                collect(&s.children, &at_rule(s), policy, directives, targets)
+            }
+            Expr::Scope(s) => {
+                if let Parent::Rule(p) = parent {
+                    if s.has_evil_amp(policy)
+                        && p.selectors.len() == 1
+                        && !p.has_evil_amp(policy)
+                        && !directives.disable(EVIL_AMPERSAND, &s.range.from)
+                    {
+                        // rules nested in a moved rule are handled by the next pass
+                        targets.push((p, s));
+                        continue;
+                    }
+                }
+                collect(&s.children, &Parent::Rule(s), policy, directives, targets);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn at_rule(scope: &Scope) -> Parent<'_> {
+    let name = scope.selectors[0].split_whitespace().next().unwrap_or("");
+    Parent::AtRule(name.to_string())
+}
+
+fn hoist(input: &str, targets: &[(&Scope, &Scope)], style: &marker::Style) -> String {
+    let mut chars: Vec<char> = input.chars().collect();
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let line_starts = line_starts(&chars);
+    let offset = |c: &Cursor| line_starts[c.row] + c.column;
+    let eol = if input.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let mut lbraces = vec![];
+    let mut string_rows = BTreeSet::new(); // rows continuing a string
+    let mut lexer = Lexer::new(chars.clone());
+    while let Some(pt) = lexer.token() {
+        match pt.token {
+            Token::LBrace => lbraces.push(offset(&pt.range.from)),
+            Token::Value(_) => string_rows.extend(pt.range.from.row + 1..=pt.range.to.row),
+            _ => (),
+        }
+    }
+    let reindent = |text: &str, row: usize, from: &str, to: &str| -> String {
+        text.split_inclusive('\n')
+            .enumerate()
+            .map(|(i, line)| match line.strip_prefix(from) {
+                Some(rest) if i > 0 && !from.is_empty() && !string_rows.contains(&(row + i)) => {
+                    format!("{}{}", to, rest)
+                }
+                _ => line.to_string(),
+            })
+            .collect()
+    };
+
+    // a parent left without declarations or rules is replaced by the
+    // moved rules
+    let emptied = |parent: &Scope| {
+        parent.children.iter().all(|e| match e {
+            Expr::Scope(s) => targets.iter().any(|(_, t)| t.range == s.range),
+            _ => false,
+        })
+    };
+
+    let mut edits: Vec<(usize, usize, String)> = vec![];
+    for (parent, scope) in targets {
+        let emptied = emptied(parent);
+        let from = offset(&scope.range.from);
+        let to = offset(&scope.range.to) + 1;
+        let lbrace = *lbraces.iter().find(|o| **o >= from).unwrap_or(&from);
+        let own_line = |c: &Cursor| {
+            chars[line_starts[c.row]..offset(c)]
+                .iter()
+                .all(|c| c == &' ' || c == &'\t')
+        };
+
+        // comments on their own lines above the rule move with it, except
+        // its marker which no longer applies
+        let leading: Vec<_> = scope
+            .comments
+            .iter()
+            .filter(|c| own_line(&c.range.from))
+            .collect();
+        let head = leading.first().map_or(&scope.range.from, |c| &c.range.from);
+
+        // remove the rule with its own lines when it stands alone
+        let mut start = offset(head);
+        let mut end = to;
+        if own_line(head) {
+            start = line_starts[head.row];
+            while end < chars.len() && (chars[end] == ' ' || chars[end] == '\t') {
+                end += 1;
+            }
+            if chars[end..].starts_with(&['\r', '\n']) {
+                end += 2;
+            } else if end < chars.len() && chars[end] == '\n' {
+                end += 1;
+            }
+        } else {
+            while end < chars.len() && chars[end] == ' ' {
+                end += 1;
+            }
+        }
+        if !emptied {
+            edits.push((start, end, "".to_string()));
+        }
+
+        let scope_indent = indent(lines[scope.range.from.row]);
+        let parent_indent = indent(lines[parent.range.from.row]);
+        let body: String = chars[lbrace..to].iter().collect();
+        let lbrace_row = line_starts.partition_point(|s| *s <= lbrace) - 1;
+        let selectors = resolver::resolve_selectors(&parent.selectors, &scope.selectors);
+        let one_line = parent.range.from.row == parent.range.to.row;
+        let separator = if one_line {
+            " ".to_string()
+        } else {
+            format!("{}{}{}", eol, eol, parent_indent)
+        };
+        let mut text = String::new();
+        for c in leading
+            .iter()
+            .filter(|c| !c.value.starts_with(&style.prefix))
+        {
+            text.push_str(&reindent(
+                &c.value,
+                c.range.from.row,
+                &scope_indent,
+                &parent_indent,
+            ));
+            if one_line {
+                text.push(' ');
+            } else {
+                text.push_str(&format!("{}{}", eol, parent_indent));
+            }
+        }
+        text.push_str(&format!(
+            "{} {}",
+            selectors.join(", "),
+            reindent(&body, lbrace_row, &scope_indent, &parent_indent)
+        ));
+        let (insert, replaced) = if emptied {
+            (offset(&parent.range.from), offset(&parent.range.to) + 1)
+        } else {
+            let end = offset(&parent.range.to) + 1;
+            (end, end)
+        };
+        match edits
+            .iter_mut()
+            .find(|(s, e, _)| *s == insert && *e == replaced)
+        {
+            Some(edit) => edit.2.push_str(&(separator + &text)),
+            None if emptied => edits.push((insert, replaced, text)),
+            None => edits.push((insert, replaced, separator + &text)),
+        }
+    }
+
+    edits.sort_by_key(|e| std::cmp::Reverse(e.0));
+    for (start, end, text) in edits {
+        chars.splice(start..end, text.chars());
+    }
+    chars.into_iter().collect()
+}
+
+fn line_starts(chars: &[char]) -> Vec<usize> {
+    let mut starts = vec![0];
+    for (i, c) in chars.iter().enumerate() {
+        if c == &'\n' {
+            starts.push(i + 1);
+        }
+    }
+    starts
+}
+
+fn indent(line: &str) -> String {
+    line.chars()
+        .take_while(|c| c == &' ' || c == &'\t')
+        .collect()
+}
+
+#[cfg(test)]
+mod fix {
+    use super::*;
+
+    fn do_fix(input: &str, expect: &str) {
+        let (output, refusals) = fix(input, &marker::Style::default(), &Policy::default()).unwrap();
+        assert_eq!(refusals, vec![]);
+        assert_eq!(output, expect);
+    }
+
+    #[test]
+    fn suffix() {
+        do_fix(
+            ".card {\n  color: red;\n  &__title {\n    color: blue;\n  }\n}\n",
+            ".card {\n  color: red;\n}\n\n.card__title {\n  color: blue;\n}\n",
+        );
+    }
+
+    #[test]
+    fn keep_children() {
+        do_fix(
+            ".a {\n  // rta: .a-b\n  &-b {\n    // note\n    .c { color: red; }\n    &:hover {}\n  }\n  &-d {}\n}",
+            ".a-b {\n  // note\n  .c { color: red; }\n  &:hover {}\n}\n\n.a-d {}",
+        );
+    }
+
+    #[test]
+    fn nested() {
+        do_fix(
+            ".p {\n  .a {\n    &-b {\n      &-c {}\n    }\n  }\n}\n",
+            ".p {\n  .a-b-c {}\n}\n",
+        );
+    }
+
+    #[test]
+    fn one_line() {
+        do_fix(
+            ".a { color: red; &-b { color: blue; } }",
+            ".a { color: red; } .a-b { color: blue; }",
+        );
+        do_fix(
+            ".a { &-b { color: blue; } } .c {}",
+            ".a-b { color: blue; } .c {}",
+        );
+    }
+
+    #[test]
+    fn leading_comments() {
+        do_fix(
+            ".a {\n  color: red; // red\n  /* b\n   * rule */\n  // sass-rta-disable-next-line other\n  // rta: .a-b\n  &-b {\n    content: \"\n  x\";\n  }\n}\n",
+            ".a {\n  color: red; // red\n}\n\n/* b\n * rule */\n// sass-rta-disable-next-line other\n.a-b {\n  content: \"\n  x\";\n}\n",
+        );
+    }
+
+    #[test]
+    fn refuse() {
+        let input = "&-a {}\n.a, .b { &-c { &-d {} } }\n.e { @media screen { &-f {} } }\n";
+        let (output, refusals) = fix(input, &marker::Style::default(), &Policy::default()).unwrap();
+        assert_eq!(output, input);
+        assert_eq!(
+            refusals
+                .iter()
+                .map(|r| (r.selectors[0].as_str(), r.reason.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("&-a", Reason::NoParent),
+                ("&-c", Reason::MultipleParentSelectors),
+                ("&-d", Reason::ParentNotFixed),
+                ("&-f", Reason::AtRule("@media".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn suppressed() {
+        for (input, expect) in &[
+            (
+                ".fade {\n  // sass-rta-disable-next-line\n  &-enter-active { x: y; }\n}\n",
+                ".fade {\n  // sass-rta-disable-next-line\n  &-enter-active { x: y; }\n}\n",
+            ),
+            (
+                ".a {\n  // sass-rta-disable\n  &-b { &-c {} }\n  // sass-rta-enable\n  &-d {}\n}\n",
+                ".a {\n  // sass-rta-disable\n  &-b { &-c {} }\n}\n\n// sass-rta-enable\n.a-d {}\n",
+            ),
+            (
+                "// sass-rta-disable-file evil-ampersand\n.a { &-b {} }\n",
+                "// sass-rta-disable-file evil-ampersand\n.a { &-b {} }\n",
+            ),
+        ] {
+            let (output, refusals) =
+                fix(input, &marker::Style::default(), &Policy::default()).unwrap();
+            assert_eq!(&output, expect);
+            assert!(refusals.iter().all(|r| r.reason == Reason::Suppressed));
+        }
+    }
+}
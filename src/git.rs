@@ -0,0 +1,180 @@
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+// A file added or modified in the index.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Staged {
+    pub root: PathBuf, // top level of the working tree
+    pub path: String,  // relative to `root`
+    pub mode: String,
+    pub blob: String,
+}
+
+impl Staged {
+    pub fn absolute(&self) -> PathBuf {
+        self.root.join(&self.path)
+    }
+
+    pub fn read(&self) -> Result<String, String> {
+        let blob = run(&self.root, &["cat-file", "blob", &self.blob], None)?;
+        String::from_utf8(blob).map_err(|e| format!("{}: {}", self.path, e))
+    }
+
+    // Replaces the staged content, leaving the working tree as it is. The
+    // content is already clean, so no filter runs on it again.
+    pub fn stage(&self, content: &str) -> Result<(), String> {
+        let blob = run(
+            &self.root,
+            &["hash-object", "-w", "--stdin", "--no-filters"],
+            Some(content.as_bytes()),
+        )?;
+        let blob = String::from_utf8_lossy(&blob).trim().to_string();
+        let info = format!("{},{},{}", self.mode, blob, self.path);
+        run(&self.root, &["update-index", "--cacheinfo", &info], None)?;
+        Ok(())
+    }
+}
+
+// Files added, copied or modified in the index of the repository holding
+// the current directory. Symbolic links and submodules are left out.
+pub fn staged() -> Result<Vec<Staged>, String> {
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    list(&toplevel(&cwd)?)
+}
+
+// Top level of the working tree holding `dir`.
+pub fn toplevel(dir: &Path) -> Result<PathBuf, String> {
+    let root = run(dir, &["rev-parse", "--show-toplevel"], None)?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&root).trim()))
+}
+
+fn list(root: &Path) -> Result<Vec<Staged>, String> {
+    let raw = run(
+        root,
+        &[
+            "diff",
+            "--cached",
+            "--raw",
+            "--no-abbrev",
+            "-z",
+            "--no-renames",
+            "--diff-filter=ACM",
+        ],
+        None,
+    )?;
+    Ok(parse_raw(root, &String::from_utf8_lossy(&raw)))
+}
+
+// Reads `:100644 100644 <blob> <blob> M\0<path>\0` entries.
+fn parse_raw(root: &Path, raw: &str) -> Vec<Staged> {
+    let mut vec = vec![];
+    let mut fields = raw.split('\0');
+    while let (Some(meta), Some(path)) = (fields.next(), fields.next()) {
+        let meta: Vec<&str> = meta.trim_start_matches(':').split(' ').collect();
+        if meta.len() < 5 || !meta[1].starts_with("100") {
+            continue;
+        }
+        vec.push(Staged {
+            root: root.to_path_buf(),
+            path: path.to_string(),
+            mode: meta[1].to_string(),
+            blob: meta[3].to_string(),
+        });
+    }
+    vec
+}
+
+fn run(dir: &Path, args: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("git: {}", e))?;
+    if let Some(input) = input {
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(input).map_err(|e| format!("git: {}", e))?;
+    }
+    drop(child.stdin.take());
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {}: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod staged {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = run(dir, args, None).unwrap();
+        String::from_utf8_lossy(&output).to_string()
+    }
+
+    #[test]
+    fn parse() {
+        let raw = ":000000 100644 0000000 1111111 A\0a.scss\0:100644 100755 2222222 3333333 M\0b c.scss\0:160000 160000 4444444 5555555 M\0sub\0";
+        let vec = parse_raw(Path::new("/repo"), raw);
+        assert_eq!(
+            vec.iter()
+                .map(|s| (s.path.as_str(), s.mode.as_str(), s.blob.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a.scss", "100644", "1111111"),
+                ("b c.scss", "100755", "3333333")
+            ]
+        );
+        assert_eq!(vec[0].absolute(), PathBuf::from("/repo/a.scss"));
+    }
+
+    #[test]
+    fn read_and_stage() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        fs::write(root.join("a.scss"), ".a {}\n").unwrap();
+        git(root, &["add", "a.scss"]);
+        fs::write(root.join("a.scss"), ".a { color: red; }\n").unwrap();
+
+        let staged = list(root).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert_eq!(staged[0].read().unwrap(), ".a {}\n");
+
+        staged[0].stage(".b {}\n").unwrap();
+        assert_eq!(git(root, &["show", ":a.scss"]), ".b {}\n");
+        assert_eq!(
+            fs::read_to_string(root.join("a.scss")).unwrap(),
+            ".a { color: red; }\n"
+        );
+    }
+
+    #[test]
+    fn stage_without_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "filter.upper.clean", "tr a-z A-Z"]);
+        fs::write(root.join(".gitattributes"), "*.scss filter=upper\n").unwrap();
+        fs::write(root.join("a.scss"), ".a {}\n").unwrap();
+        git(root, &["add", "a.scss"]);
+
+        let staged = list(root).unwrap();
+        assert_eq!(staged[0].read().unwrap(), ".A {}\n");
+        staged[0].stage(".A {}\n.b {}\n").unwrap();
+        assert_eq!(git(root, &["show", ":a.scss"]), ".A {}\n.b {}\n");
+    }
+}
@@ -0,0 +1,228 @@
+use crate::checker;
+use crate::finder;
+use crate::finder::Location;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use std::collections::BTreeMap;
+
+pub const FILE_NAME: &str = ".sass-rta-index.json";
+
+const VERSION: u32 = 3;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub file: String,
+    pub location: Location,
+}
+
+// Classes and ids resolved in the indexed files, keyed the way
+// `finder::builds` matches queries, with the content hashes telling
+// whether a file changed since it was indexed.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Index {
+    pub version: u32,
+    pub files: BTreeMap<String, String>,
+    pub names: BTreeMap<String, Vec<Entry>>,
+}
+
+impl Default for Index {
+    fn default() -> Index {
+        Index {
+            version: VERSION,
+            files: BTreeMap::new(),
+            names: BTreeMap::new(),
+        }
+    }
+}
+
+impl Index {
+    pub fn read(content: &str) -> Result<Index, String> {
+        let index: Index = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        if index.version != VERSION {
+            return Err(format!("unsupported index version {}", index.version));
+        }
+        Ok(index)
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).unwrap() + "\n"
+    }
+
+    pub fn is_fresh(&self, file: &str, content: &str) -> bool {
+        self.files.get(&normalize(file)) == Some(&hash(content))
+    }
+
+    // Reparses the file unless its content is unchanged. Returns whether it
+    // was reparsed.
+    pub fn update(&mut self, file: &str, content: &str) -> bool {
+        if self.is_fresh(file, content) {
+            return false;
+        }
+        let file = normalize(file);
+        self.remove(&file);
+
+        let chars: Vec<char> = content.chars().collect();
+        let (exprs, _) = Parser::new(Lexer::new(chars.clone())).parse();
+        let tokens = checker::tokens(chars);
+        for built in finder::built(&exprs, &tokens) {
+            for name in finder::added(&built.resolved, &built.parent) {
+                self.names.entry(name).or_default().push(Entry {
+                    file: file.clone(),
+                    location: Location {
+                        range: built.range.clone(),
+                        selector: built.selector.to_string(),
+                        resolved: built.resolved.clone(),
+                    },
+                });
+            }
+        }
+        self.files.insert(file, hash(content));
+        true
+    }
+
+    // Drops the files other than the given ones. Returns how many were
+    // dropped.
+    pub fn retain(&mut self, files: &[String]) -> usize {
+        let files: Vec<String> = files.iter().map(|f| normalize(f)).collect();
+        let removed: Vec<String> = self
+            .files
+            .keys()
+            .filter(|f| !files.contains(f))
+            .cloned()
+            .collect();
+        for file in &removed {
+            self.remove(file);
+        }
+        removed.len()
+    }
+
+    pub fn contains(&self, file: &str) -> bool {
+        self.files.contains_key(&normalize(file))
+    }
+
+    // Locations building the class or id of `query`, by indexed file.
+    pub fn find(&self, query: &str) -> BTreeMap<String, Vec<Location>> {
+        let mut found: BTreeMap<String, Vec<Location>> = BTreeMap::new();
+        for e in self
+            .names
+            .get(&finder::normalize(query))
+            .into_iter()
+            .flatten()
+        {
+            found
+                .entry(e.file.clone())
+                .or_default()
+                .push(e.location.clone());
+        }
+        found
+    }
+
+    fn remove(&mut self, file: &str) {
+        self.files.remove(file);
+        for entries in self.names.values_mut() {
+            entries.retain(|e| e.file != file);
+        }
+        self.names.retain(|_, entries| !entries.is_empty());
+    }
+}
+
+fn hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn normalize(file: &str) -> String {
+    file.replace('\\', "/").trim_start_matches("./").to_string()
+}
+
+#[cfg(test)]
+mod update {
+    use super::*;
+
+    const A: &str = ".card {\n  &__title {\n    &--active {}\n  }\n}\n";
+    const B: &str = ".card {\n  &__body {}\n}\n";
+
+    fn found(index: &Index, file: &str, query: &str) -> Vec<Location> {
+        index
+            .find(query)
+            .remove(&normalize(file))
+            .unwrap_or_default()
+    }
+
+    fn rows(locations: Vec<Location>) -> Vec<usize> {
+        locations.iter().map(|l| l.range.from.row).collect()
+    }
+
+    #[test]
+    fn find() {
+        let mut index = Index::default();
+        assert!(index.update("./a.scss", A));
+        assert!(index.update("b.scss", B));
+        let index = Index::read(&index.json()).unwrap();
+
+        assert_eq!(
+            rows(found(&index, "a.scss", "card__title--active")),
+            vec![2]
+        );
+        assert_eq!(rows(found(&index, "./a.scss", ".card")), vec![0]);
+        assert_eq!(rows(found(&index, "b.scss", ".card")), vec![0]);
+        assert!(found(&index, "b.scss", ".card__title").is_empty());
+        for query in &[".card__title--active", ".card", ".card__title", ".x"] {
+            assert_eq!(
+                found(&index, "a.scss", query),
+                finder::find(A, query),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn incremental() {
+        let mut index = Index::default();
+        index.update("a.scss", A);
+        index.update("b.scss", B);
+        assert!(index.is_fresh("a.scss", A));
+        assert!(!index.update("a.scss", A));
+
+        let changed = ".box {\n  &__title {}\n}\n";
+        assert!(!index.is_fresh("a.scss", changed));
+        assert!(index.update("a.scss", changed));
+        assert!(found(&index, "a.scss", ".card__title").is_empty());
+        assert_eq!(rows(found(&index, "a.scss", ".box__title")), vec![1]);
+
+        assert_eq!(index.retain(&["./a.scss".to_string()]), 1);
+        assert!(found(&index, "b.scss", ".card").is_empty());
+        assert!(!index.contains("b.scss"));
+        assert!(index.names.values().flatten().all(|e| e.file == "a.scss"));
+    }
+
+    #[test]
+    fn same_as_finder() {
+        let input = ".md\\:flex {\n  &-col {}\n}\n[data-x=\".y\"] {\n  &.z {}\n}\n";
+        let mut index = Index::default();
+        index.update("a.scss", input);
+        for query in &["md\\:flex-col", ".md\\:flex", "md", ".y", ".z", "#x"] {
+            assert_eq!(
+                found(&index, "a.scss", query),
+                finder::find(input, query),
+                "{}",
+                query
+            );
+        }
+        assert_eq!(rows(found(&index, "a.scss", "md\\:flex-col")), vec![1]);
+    }
+
+    #[test]
+    fn version() {
+        assert!(Index::read("{\"version\": 0, \"files\": {}, \"names\": {}}").is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Deserialize;
+use serde::Serialize;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Value(String),   // selector property value
@@ -15,7 +18,7 @@ pub struct PToken {
     pub range: Range,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct Cursor {
     pub row: usize,
     pub column: usize,
@@ -25,7 +28,7 @@ impl Cursor {
         Cursor { row, column }
     }
 }
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Range {
     pub from: Cursor,
     pub to: Cursor,
@@ -45,6 +48,12 @@ pub struct Lexer {
     escaping: bool,
 }
 
+#[allow(
+    clippy::needless_return,
+    clippy::match_ref_pats,
+    clippy::match_like_matches_macro,
+    clippy::assign_op_pattern
+)]
 impl Lexer {
     pub fn new(input: Vec<char>) -> Lexer {
         Lexer {
@@ -113,7 +122,8 @@ impl Lexer {
             self.next();
             line_comment = line_comment + &self.curr()?.to_string();
 
-            if self.peek()? == &'*' && self.peek_peek().is_some() && self.peek_peek()? == &'/' {
+            // an unterminated comment runs to the end of the input
+            if self.peek() == Some(&'*') && self.peek_peek() == Some(&'/') {
                 line_comment = line_comment + "*/";
                 self.next();
                 self.next();
@@ -151,11 +161,19 @@ impl Lexer {
     }
 
     fn token_value(&mut self) -> Option<Token> {
-        let mut value = self.curr()?.to_string();
+        let mut value = if self.is_interpolation() {
+            self.token_interpolation()?
+        } else {
+            self.curr()?.to_string()
+        };
         while self.peek().is_some() && (self.escaping || Self::is_value(self.peek().unwrap())) {
             self.next();
 
-            if self.curr() == Some(&'\'') {
+            if self.is_interpolation() {
+                value = value + &self.token_interpolation()?;
+            } else if value.ends_with("url(") && !matches!(self.curr(), Some('\'') | Some('"')) {
+                value = value + &self.token_url()?;
+            } else if self.curr() == Some(&'\'') {
                 value = match self.token_single_quote_value()? {
                     Token::Value(v) => value + &v,
                     _ => value,
@@ -178,6 +196,38 @@ impl Lexer {
         return Some(Token::Value(value));
     }
 
+    fn is_interpolation(&mut self) -> bool {
+        !self.escaping && self.curr() == Some(&'#') && self.peek() == Some(&'{')
+    }
+
+    // `#{...}` up to its matching `}`, which may hold any character.
+    fn token_interpolation(&mut self) -> Option<String> {
+        let mut value = self.curr()?.to_string();
+        let mut depth = 0;
+        while self.peek().is_some() {
+            self.next();
+            let c = *self.curr()?;
+            value.push(c);
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 1 => break,
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+        Some(value)
+    }
+
+    // Unquoted argument of `url(`, which may hold `:` and `//`.
+    fn token_url(&mut self) -> Option<String> {
+        let mut value = self.curr()?.to_string();
+        while self.curr()? != &')' && self.peek().is_some_and(|c| *c != '\n') {
+            self.next();
+            value.push(*self.curr()?);
+        }
+        Some(value)
+    }
+
     fn next(&mut self) {
         if self.curr() == Some(&'\n') {
             self.column = 0;
@@ -445,6 +495,40 @@ mod property {
     }
 }
 
+#[cfg(test)]
+mod interpolation {
+    use super::*;
+
+    #[test]
+    fn selector() {
+        let mut lexer = Lexer::new(".a-#{$b + {c: d}} #{$e} {".chars().collect());
+        assert_eq!(
+            lexer.token().unwrap().token,
+            Token::Value(".a-#{$b + {c: d}}".to_string())
+        );
+        assert_eq!(
+            lexer.token().unwrap(),
+            PToken {
+                token: Token::Value("#{$e}".to_string()),
+                range: Range::new(Cursor::new(0, 18), Cursor::new(0, 22)),
+            }
+        );
+        assert_eq!(lexer.token().unwrap().token, Token::LBrace);
+        assert_eq!(lexer.token(), None);
+    }
+
+    #[test]
+    fn url() {
+        let mut lexer = Lexer::new("url(http://a.com/b.png);".chars().collect());
+        assert_eq!(
+            lexer.token().unwrap().token,
+            Token::Value("url(http://a.com/b.png)".to_string())
+        );
+        assert_eq!(lexer.token().unwrap().token, Token::Semicolon);
+        assert_eq!(lexer.token(), None);
+    }
+}
+
 #[cfg(test)]
 mod line_comment {
     use super::*;
@@ -489,4 +573,16 @@ mod block_comment {
         assert_eq!(lexer.token().unwrap().token, Token::LBrace);
         assert_eq!(lexer.token(), None);
     }
+
+    #[test]
+    fn unterminated() {
+        let mut lexer = Lexer::new(".a { /* abc }".chars().collect());
+        assert_eq!(lexer.token().unwrap().token, Token::Value(".a".to_string()));
+        assert_eq!(lexer.token().unwrap().token, Token::LBrace);
+        assert_eq!(
+            lexer.token().unwrap().token,
+            Token::Comment("/* abc }".to_string())
+        );
+        assert_eq!(lexer.token(), None);
+    }
 }
@@ -2,43 +2,728 @@
 extern crate lazy_static;
 
 use clap::App;
+use clap::Arg;
+use clap::ArgMatches;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
 mod ambuster;
+mod baseline;
+mod checker;
+mod config;
+mod diff;
 mod expression;
+mod finder;
+mod fixer;
+mod git;
+mod index;
 mod lexer;
+mod marker;
 mod parser;
+mod report;
+mod resolver;
+mod suppress;
+mod tags;
+mod walker;
+
+const EXIT_FINDINGS: i32 = 1;
+const EXIT_ERROR: i32 = 2;
 
 fn main() {
     let matches = App::new("sass-rta")
         .version("0.0.1")
         .about("Does awesome things")
-        .arg("-d, --dry-run 'Only prints results'")
+        .arg("-d, --dry-run 'Only prints the diff of results'")
         .subcommand(
             App::new("write")
                 .about("write markings")
-                .arg("<target> 'Sets an optional target file'"),
+                .args(target_args())
+                .arg(format_arg()),
         )
         .subcommand(
             App::new("reset")
                 .about("reset markings")
-                .arg("<target> 'Sets an optional target file'"),
+                .args(target_args()),
+        )
+        .subcommand(
+            App::new("check")
+                .about("report evil ampersands")
+                .args(target_args())
+                .arg("--max-warnings=[N] 'Number of warnings to allow before failing, unused suppressions included'")
+                .arg(
+                    Arg::new("baseline")
+                        .long("baseline")
+                        .value_name("FILE")
+                        .about("Reports only findings missing from the baseline")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("write-baseline")
+                        .long("write-baseline")
+                        .value_name("FILE")
+                        .about("Records the current findings as the baseline")
+                        .takes_value(true)
+                        .conflicts_with_all(&["baseline", "diff"]),
+                )
+                .arg(
+                    Arg::new("diff")
+                        .long("diff")
+                        .value_name("FILE")
+                        .about("Reports only rules touched by the unified diff, `-` for stdin")
+                        .takes_value(true),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            App::new("verify")
+                .about("report stale markings")
+                .args(target_args()),
+        )
+        .subcommand(
+            App::new("update")
+                .about("rewrite stale markings")
+                .args(target_args()),
+        )
+        .subcommand(
+            App::new("find")
+                .about("find rules building a class or id")
+                .arg(
+                    Arg::new("query")
+                        .about("Class or id to find, e.g. `.card__title--active`")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("target")
+                        .about("Target files, directories or glob patterns")
+                        .multiple(true)
+                        .default_value("."),
+                )
+                .args(pattern_args())
+                .arg(index_arg()),
+        )
+        .subcommand(
+            App::new("index")
+                .about("build or refresh the index used by find")
+                .arg(
+                    Arg::new("target")
+                        .about("Target files, directories or glob patterns")
+                        .multiple(true)
+                        .default_value("."),
+                )
+                .args(pattern_args())
+                .arg(index_arg()),
+        )
+        .subcommand(
+            App::new("tags")
+                .about("generate a tags file of resolved classes and ids")
+                .arg(
+                    Arg::new("target")
+                        .about("Target files, directories or glob patterns")
+                        .multiple(true)
+                        .default_value("."),
+                )
+                .args(pattern_args())
+                .arg("-o, --output=[FILE] 'File to write, `-` for stdout [default: tags, or TAGS with --etags]'")
+                .arg("--etags 'Writes the Emacs etags format'"),
+        )
+        .subcommand(
+            App::new("fix")
+                .about("un-nest evil ampersand rules")
+                .args(target_args()),
         )
         .get_matches();
 
-    if matches.is_present("dry-run") {
-        println!("[dry run]");
+    let dry_run = matches.is_present("dry-run");
+    let loader = config::Loader::new();
+
+    if let Some(matches) = matches.subcommand_matches("write") {
+        // the diffs of a dry run would be mixed into the report on stdout
+        let format = format(matches, &loader);
+        if dry_run && format != "text" {
+            eprintln!("--dry-run cannot be used with the {} format", format);
+            process::exit(EXIT_ERROR);
+        }
+
+        let files = files(matches, &loader);
+        let mut records = vec![];
+        let edited = edit_all(&files, dry_run, |path, input| {
+            let config = config_of(&loader, path);
+            let output = marker::write(input, &config.marker, &config.ampersand)?;
+            records.extend(records_of(path, input, &config, None));
+            Ok(output)
+        });
+        match format.as_str() {
+            "json" => print!("{}", report::json(&records)),
+            "sarif" => print!("{}", report::sarif(&records)),
+            _ => (),
+        }
+        exit_after_edit(dry_run, edited);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("reset") {
+        let edited = edit_all(&files(matches, &loader), dry_run, |path, input| {
+            Ok(marker::reset(input, &config_of(&loader, path).marker))
+        });
+        exit_after_edit(dry_run, edited);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("check") {
+        let max_warnings = match matches.value_of("max-warnings").map(str::parse::<usize>) {
+            Some(Ok(n)) => n,
+            Some(Err(e)) => {
+                eprintln!("--max-warnings: {}", e);
+                process::exit(EXIT_ERROR);
+            }
+            None => 0,
+        };
+
+        let changes = matches.value_of("diff").map(|path| {
+            let diff = if path == "-" {
+                read_stdin()
+            } else {
+                read(path)
+            };
+            // paths of diffs are relative to the top level of the working
+            // tree, or to the current directory outside of one
+            let cwd = std::env::current_dir().unwrap_or_default();
+            let root = git::toplevel(&cwd).unwrap_or(cwd);
+            diff::Changes::parse(&diff, &root).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                process::exit(EXIT_ERROR);
+            })
+        });
+
+        let files = files(matches, &loader);
+        let mut records = vec![];
+        let mut broken = 0;
+        for file in &files {
+            let path = file.path();
+            let input = file.read();
+            if has_parse_errors(&path, &input) {
+                broken += 1;
+            }
+            let config = config_of(&loader, &path);
+            records.extend(records_of(&path, &input, &config, changes.as_ref()));
+        }
+
+        if let Some(path) = matches.value_of("write-baseline") {
+            save(path, &baseline::Baseline::new(&records).json());
+            eprintln!("{} findings written to {}", records.len(), path);
+            exit_on_parse_errors(broken);
+            return;
+        }
+        if let Some(path) = matches.value_of("baseline") {
+            let baseline = baseline::Baseline::read(&read(path)).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                process::exit(EXIT_ERROR);
+            });
+            let checked: Vec<String> = files.iter().map(Input::path).collect();
+            let total = records.len();
+            let (new, stale) = baseline.compare(records, &checked);
+            for e in &stale {
+                eprintln!(
+                    "{}: stale entry `{}` of {} in {}",
+                    path, e.selector, e.rule, e.file
+                );
+            }
+            eprintln!(
+                "{} findings in baseline, {} stale entries",
+                total - new.len(),
+                stale.len()
+            );
+            records = new;
+        }
+        match format(matches, &loader).as_str() {
+            "json" => print!("{}", report::json(&records)),
+            "sarif" => print!("{}", report::sarif(&records)),
+            _ => {
+                print!("{}", report::text(&records));
+                eprintln!("{} findings in {} files", records.len(), files.len());
+            }
+        }
+        exit_on_parse_errors(broken);
+        // errors always fail, warnings only beyond the allowed number
+        let errors = records
+            .iter()
+            .filter(|r| r.severity == config::Severity::Error)
+            .count();
+        if errors > 0 || records.len() - errors > max_warnings {
+            process::exit(EXIT_FINDINGS);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("verify") {
+        let files = files(matches, &loader);
+        let mut count = 0;
+        let mut broken = 0;
+        for file in &files {
+            let path = file.path();
+            let input = file.read();
+            if has_parse_errors(&path, &input) {
+                broken += 1;
+            }
+            let config = config_of(&loader, &path);
+            for s in marker::stale(&input, &config.marker, &config.ampersand) {
+                match s.expected {
+                    Some(expected) => println!(
+                        "{}:{}: stale marker `{}`, expected `{}`",
+                        path,
+                        s.row + 1,
+                        s.found,
+                        expected
+                    ),
+                    None => println!(
+                        "{}:{}: marker `{}` above no evil rule",
+                        path,
+                        s.row + 1,
+                        s.found
+                    ),
+                }
+                count += 1;
+            }
+        }
+        eprintln!("{} stale markers in {} files", count, files.len());
+        exit_on_parse_errors(broken);
+        if count > 0 {
+            process::exit(EXIT_FINDINGS);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("update") {
+        let edited = edit_all(&files(matches, &loader), dry_run, |path, input| {
+            let config = config_of(&loader, path);
+            marker::update(input, &config.marker, &config.ampersand)
+        });
+        exit_after_edit(dry_run, edited);
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("write") {
-        if let Some(o) = matches.value_of("target") {
-            println!("write: Value for target: {}", o);
+    if let Some(matches) = matches.subcommand_matches("find") {
+        let query = matches.value_of("query").unwrap();
+        let index = index_of(matches);
+        let found = index.as_ref().map(|i| i.find(query)).unwrap_or_default();
+        let mut count = 0;
+        let mut broken = 0;
+        for file in files(matches, &loader) {
+            let path = file.path();
+            // indexed files are only read when the index finds the query in
+            // them, and resolved again when they changed since
+            let hit = found.get(&index::normalize(&path));
+            if hit.is_none() && index.iter().any(|i| i.contains(&path)) {
+                continue;
+            }
+            let input = file.read();
+            let locations = match hit {
+                Some(locations) if index.iter().any(|i| i.is_fresh(&path, &input)) => {
+                    locations.clone()
+                }
+                _ => {
+                    if has_parse_errors(&path, &input) {
+                        broken += 1;
+                    }
+                    finder::find(&input, query)
+                }
+            };
+            for l in locations {
+                println!(
+                    "{}:{}:{}: `{}` builds `{}`",
+                    path,
+                    l.range.from.row + 1,
+                    l.range.from.column + 1,
+                    l.selector,
+                    l.resolved
+                );
+                count += 1;
+            }
+        }
+        exit_on_parse_errors(broken);
+        if count == 0 {
+            eprintln!("`{}` not found", finder::normalize(query));
+            process::exit(EXIT_FINDINGS);
         }
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("reset") {
-        if let Some(o) = matches.value_of("target") {
-            println!("reset: Value for target: {}", o);
+    if let Some(matches) = matches.subcommand_matches("index") {
+        let mut index = index_of(matches).unwrap_or_default();
+        let files = files(matches, &loader);
+        let mut reparsed = 0;
+        let mut broken = 0;
+        for file in &files {
+            let path = file.path();
+            let input = file.read();
+            if index.update(&path, &input) {
+                reparsed += 1;
+                if has_parse_errors(&path, &input) {
+                    broken += 1;
+                }
+            }
         }
+        let removed = index.retain(&files.iter().map(Input::path).collect::<Vec<_>>());
+        save(index_path(matches), &index.json());
+        eprintln!(
+            "{} files indexed, {} reparsed, {} removed",
+            files.len(),
+            reparsed,
+            removed
+        );
+        exit_on_parse_errors(broken);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("tags") {
+        let etags = matches.is_present("etags");
+        let mut tags = vec![];
+        let mut broken = 0;
+        for file in files(matches, &loader) {
+            let path = file.path();
+            let input = file.read();
+            if has_parse_errors(&path, &input) {
+                broken += 1;
+            }
+            tags.extend(tags::collect(&path, &input));
+        }
+        let output = if etags {
+            tags::etags(&tags)
+        } else {
+            tags::ctags(&tags)
+        };
+        match matches.value_of("output") {
+            Some("-") => print!("{}", output),
+            Some(path) => save(path, &output),
+            None if etags => save("TAGS", &output),
+            None => save("tags", &output),
+        }
+        eprintln!("{} tags", tags.len());
+        exit_on_parse_errors(broken);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("fix") {
+        let edited = edit_all(&files(matches, &loader), dry_run, |path, input| {
+            let config = config_of(&loader, path);
+            let (output, refusals) = fixer::fix(input, &config.marker, &config.ampersand)?;
+            for r in refusals {
+                eprintln!(
+                    "{}: cannot fix `{}` at {}:{}: {}",
+                    path,
+                    r.selectors.join(", "),
+                    r.range.from.row + 1,
+                    r.range.from.column + 1,
+                    r.reason.message()
+                );
+            }
+            Ok(output)
+        });
+        exit_after_edit(dry_run, edited);
     }
 
     // Continued program logic goes here...
 }
+
+fn target_args() -> Vec<Arg<'static>> {
+    let mut args = vec![
+        Arg::new("target")
+            .about("Target files, directories or glob patterns")
+            .required_unless_present("staged")
+            .multiple(true),
+        Arg::new("staged").long("staged").about(
+            "Processes the staged content of the files staged in git, within the targets if any",
+        ),
+    ];
+    args.extend(pattern_args());
+    args
+}
+
+fn pattern_args() -> Vec<Arg<'static>> {
+    vec![
+        Arg::new("include")
+            .long("include")
+            .about("File patterns to pick up in directories [default: *.scss]")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .number_of_values(1),
+        Arg::new("exclude")
+            .long("exclude")
+            .about("Patterns of files and directories to skip")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .number_of_values(1),
+    ]
+}
+
+// A file to process, in the working tree or staged in the index.
+enum Input {
+    File(PathBuf),
+    Staged(git::Staged, String), // with its path from the current directory
+}
+
+impl Input {
+    fn path(&self) -> String {
+        match self {
+            Input::File(path) => path.to_string_lossy().to_string(),
+            Input::Staged(_, path) => path.clone(),
+        }
+    }
+
+    fn read(&self) -> String {
+        match self {
+            Input::File(_) => read(&self.path()),
+            Input::Staged(staged, path) => staged.read().unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                process::exit(EXIT_ERROR);
+            }),
+        }
+    }
+
+    // Saves the content, and for a staged file also into the working tree
+    // unless it has changes of its own.
+    fn save(&self, old: &str, content: &str) {
+        let path = self.path();
+        match self {
+            Input::File(_) => save(&path, content),
+            Input::Staged(staged, _) => {
+                staged.stage(content).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", path, e);
+                    process::exit(EXIT_ERROR);
+                });
+                if fs::read_to_string(&path).is_ok_and(|tree| tree == old) {
+                    save(&path, content);
+                } else {
+                    eprintln!("{}: working tree has unstaged changes, left as is", path);
+                }
+            }
+        }
+    }
+}
+
+// Expands the targets, or lists the staged files within them, with the
+// include and exclude patterns of the configs, which the command line ones
+// override.
+fn files(matches: &ArgMatches, loader: &config::Loader) -> Vec<Input> {
+    let values = |name| -> Vec<String> {
+        matches
+            .values_of(name)
+            .map_or(vec![], |v| v.map(str::to_string).collect())
+    };
+    let targets = values("target");
+    let include = matches.is_present("include").then(|| values("include"));
+    let exclude = matches.is_present("exclude").then(|| values("exclude"));
+    let loader = loader.clone();
+    let options_for: walker::OptionsFor = Arc::new(move |path: &Path| {
+        let mut options = loader.load(path)?.walker_options();
+        if let Some(include) = &include {
+            options.include = walker::Patterns::new(None, include.clone());
+        }
+        if let Some(exclude) = &exclude {
+            options.exclude = walker::Patterns::new(None, exclude.clone());
+        }
+        Ok(options)
+    });
+
+    let targets: Vec<&str> = targets.iter().map(String::as_str).collect();
+    let inputs = if matches.is_present("staged") {
+        staged_files(&targets, &*options_for)
+    } else {
+        walker::files(&targets, options_for).map(|v| v.into_iter().map(Input::File).collect())
+    };
+    inputs.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_ERROR);
+    })
+}
+
+fn staged_files(
+    targets: &[&str],
+    options_for: &dyn Fn(&Path) -> Result<walker::Options, String>,
+) -> Result<Vec<Input>, String> {
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    let targets = targets
+        .iter()
+        .map(std::path::absolute)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut staged = git::staged()?;
+    staged.retain(|s| targets.is_empty() || targets.iter().any(|t| s.absolute().starts_with(t)));
+
+    let paths = walker::filter(
+        staged.iter().map(git::Staged::absolute).collect(),
+        options_for,
+    )?;
+    Ok(staged
+        .into_iter()
+        .filter(|s| paths.contains(&s.absolute()))
+        .map(|s| {
+            let absolute = s.absolute();
+            let path = absolute.strip_prefix(&cwd).unwrap_or(&absolute);
+            let path = path.to_string_lossy().to_string();
+            Input::Staged(s, path)
+        })
+        .collect())
+}
+
+fn index_arg() -> Arg<'static> {
+    Arg::new("index")
+        .long("index")
+        .value_name("FILE")
+        .about("Index file [default: .sass-rta-index.json]")
+        .takes_value(true)
+}
+
+fn index_path(matches: &ArgMatches) -> &str {
+    matches.value_of("index").unwrap_or(index::FILE_NAME)
+}
+
+// The index if any. A broken one is ignored, to be rebuilt by `index`.
+fn index_of(matches: &ArgMatches) -> Option<index::Index> {
+    let path = index_path(matches);
+    let content = fs::read_to_string(path).ok()?;
+    index::Index::read(&content)
+        .map_err(|e| eprintln!("{}: {}, ignored", path, e))
+        .ok()
+}
+
+fn config_of(loader: &config::Loader, path: &str) -> config::Config {
+    loader.load(Path::new(path)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(EXIT_ERROR);
+    })
+}
+
+fn format_arg() -> Arg<'static> {
+    Arg::new("format")
+        .long("format")
+        .about("Output format of findings [default: the format configured for the first target, or text]")
+        .takes_value(true)
+        .possible_values(&["text", "json", "sarif"])
+}
+
+// The format given on the command line, or the one configured for the
+// first target.
+fn format(matches: &ArgMatches, loader: &config::Loader) -> String {
+    if let Some(format) = matches.value_of("format") {
+        return format.to_string();
+    }
+    let target = matches.value_of("target").unwrap_or(".");
+    config_of(loader, target)
+        .format
+        .unwrap_or_else(|| "text".to_string())
+}
+
+// Findings of the rules enabled for the file, limited to the rules touched
+// by `changes` if any.
+fn records_of(
+    path: &str,
+    input: &str,
+    config: &config::Config,
+    changes: Option<&diff::Changes>,
+) -> Vec<report::Record> {
+    checker::check(input, &config.ampersand)
+        .iter()
+        .filter(|f| changes.is_none_or(|c| c.touches(path, &f.scope)))
+        .map(|f| report::Record::new(path, input, f, config.severity(f.rule)))
+        .filter(|r| r.severity != config::Severity::Off)
+        .collect()
+}
+
+// Applies `f` to each file, or prints the diffs it would make on dry run.
+// Files for which `f` gives parse errors are left as they are. Returns the
+// number of files whose content changes, and of the ones left for parse
+// errors.
+fn edit_all(
+    files: &[Input],
+    dry_run: bool,
+    mut f: impl FnMut(&str, &str) -> Result<String, Vec<parser::ParseError>>,
+) -> (usize, usize) {
+    let mut changed = 0;
+    let mut broken = 0;
+    for file in files {
+        let path = file.path();
+        let input = file.read();
+        let output = match f(&path, &input) {
+            Ok(output) => output,
+            Err(errors) => {
+                print_parse_errors(&path, &errors);
+                eprintln!("{}: left as is because of parse errors", path);
+                broken += 1;
+                continue;
+            }
+        };
+        if output == input {
+            continue;
+        }
+
+        changed += 1;
+        if dry_run {
+            print!("{}", diff::unified(&path, &input, &output));
+        } else {
+            file.save(&input, &output);
+            eprintln!("{}: updated", path);
+        }
+    }
+
+    let verb = if dry_run {
+        "would be updated"
+    } else {
+        "updated"
+    };
+    eprintln!("{} of {} files {}", changed, files.len(), verb);
+    (changed, broken)
+}
+
+fn exit_after_edit(dry_run: bool, (changed, broken): (usize, usize)) {
+    exit_on_parse_errors(broken);
+    if dry_run && changed > 0 {
+        process::exit(EXIT_FINDINGS);
+    }
+}
+
+// Prints the parse errors of the file. Returns whether there are any.
+fn has_parse_errors(path: &str, input: &str) -> bool {
+    let errors = parser::errors(input);
+    print_parse_errors(path, &errors);
+    !errors.is_empty()
+}
+
+fn print_parse_errors(path: &str, errors: &[parser::ParseError]) {
+    for e in errors {
+        eprintln!(
+            "{}:{}:{}: parse error: {}",
+            path,
+            e.range.from.row + 1,
+            e.range.from.column + 1,
+            e.message
+        );
+    }
+}
+
+fn exit_on_parse_errors(broken: usize) {
+    if broken > 0 {
+        eprintln!("{} files with parse errors", broken);
+        process::exit(EXIT_ERROR);
+    }
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(EXIT_ERROR);
+    })
+}
+
+fn read_stdin() -> String {
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .unwrap_or_else(|e| {
+            eprintln!("stdin: {}", e);
+            process::exit(EXIT_ERROR);
+        });
+    content
+}
+
+fn save(path: &str, content: &str) {
+    fs::write(path, content).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(EXIT_ERROR);
+    })
+}
@@ -0,0 +1,364 @@
+use crate::ambuster::Policy;
+use crate::expression::Expr;
+use crate::lexer::Lexer;
+use crate::lexer::Token;
+use crate::parser;
+use crate::parser::ParseError;
+use crate::parser::Parser;
+use crate::resolver;
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub const PREFIX: &str = "// rta:";
+pub const FORMAT: &str = "{prefix} {selectors}";
+
+// How markers look. `format` must start with `{prefix}` so that the
+// markers can be found again.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Style {
+    pub prefix: String,
+    pub format: String,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            prefix: PREFIX.to_string(),
+            format: FORMAT.to_string(),
+        }
+    }
+}
+
+impl Style {
+    pub fn render(&self, selectors: &[String]) -> String {
+        self.format
+            .replace("{prefix}", &self.prefix)
+            .replace("{selectors}", &selectors.join(", "))
+    }
+}
+
+// Inserts a marker comment holding the resolved selectors above every rule
+// built with an evil ampersand. An existing marker right above the rule is
+// replaced, so writing twice gives the same result. Input with parse
+// errors is refused.
+pub fn write(input: &str, style: &Style, policy: &Policy) -> Result<String, Vec<ParseError>> {
+    let markers = markers(&parser::parse(input)?, policy);
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let marker_rows = marker_rows(input, &lines, &style.prefix);
+    let mut output = String::new();
+    for (row, line) in lines.iter().enumerate() {
+        if marker_rows.contains(&row) && markers.contains_key(&(row + 1)) {
+            continue;
+        }
+        if let Some(selectors) = markers.get(&row) {
+            output.push_str(&marker_line(line, &style.render(selectors)));
+        }
+        output.push_str(line);
+    }
+    Ok(output)
+}
+
+// A marker whose text differs from the resolution of the rule below it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Stale {
+    pub row: usize,
+    pub found: String,
+    pub expected: Option<String>, // none when no evil rule is below
+}
+
+// Finds the markers which went stale since they were written, e.g. by
+// renaming a parent rule.
+pub fn stale(input: &str, style: &Style, policy: &Policy) -> Vec<Stale> {
+    let (exprs, _) = Parser::new(Lexer::new(input.chars().collect())).parse();
+    stale_markers(input, &markers(&exprs, policy), style)
+}
+
+fn stale_markers(input: &str, markers: &BTreeMap<usize, Vec<String>>, style: &Style) -> Vec<Stale> {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    marker_rows(input, &lines, &style.prefix)
+        .into_iter()
+        .filter_map(|row| {
+            let found = lines[row].trim();
+            let expected = markers.get(&(row + 1)).map(|s| style.render(s));
+            match &expected {
+                Some(e) if e.trim() == found => None,
+                _ => Some(Stale {
+                    row,
+                    found: found.to_string(),
+                    expected,
+                }),
+            }
+        })
+        .collect()
+}
+
+// Rewrites the stale markers in place and removes the ones above no evil
+// rule, leaving the other lines as they are. Input with parse errors is
+// refused.
+pub fn update(input: &str, style: &Style, policy: &Policy) -> Result<String, Vec<ParseError>> {
+    let markers = markers(&parser::parse(input)?, policy);
+    let stale: BTreeMap<usize, Option<String>> = stale_markers(input, &markers, style)
+        .into_iter()
+        .map(|s| (s.row, s.expected))
+        .collect();
+    let mut output = String::new();
+    for (row, line) in input.split_inclusive('\n').enumerate() {
+        match stale.get(&row) {
+            Some(Some(expected)) => output.push_str(&marker_line(line, expected)),
+            Some(None) => (),
+            None => output.push_str(line),
+        }
+    }
+    Ok(output)
+}
+
+// Resolved selectors of the rules built with an evil ampersand, by the row
+// where the rules start.
+fn markers(exprs: &[Expr], policy: &Policy) -> BTreeMap<usize, Vec<String>> {
+    let mut markers: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for resolved in resolver::resolve(exprs) {
+        if resolved.scope.has_evil_amp(policy) {
+            markers
+                .entry(resolved.scope.range.from.row)
+                .or_default()
+                .extend(resolved.selectors);
+        }
+    }
+    markers
+}
+
+// Removes the lines holding markers written by `write`, leaving every other
+// comment as is.
+pub fn reset(input: &str, style: &Style) -> String {
+    let lines: Vec<&str> = input.split_inclusive('\n').collect();
+    let marker_rows = marker_rows(input, &lines, &style.prefix);
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(row, _)| !marker_rows.contains(row))
+        .map(|(_, line)| *line)
+        .collect()
+}
+
+// Rows of the marker comments which stand alone on their line.
+pub fn marker_rows(input: &str, lines: &[&str], prefix: &str) -> BTreeSet<usize> {
+    let mut lexer = Lexer::new(input.chars().collect());
+    let mut rows = BTreeSet::new();
+    while let Some(pt) = lexer.token() {
+        if let Token::Comment(comment) = pt.token {
+            let row = pt.range.from.row;
+            let leading: String = lines[row].chars().take(pt.range.from.column).collect();
+            if comment.starts_with(prefix) && leading.trim().is_empty() {
+                rows.insert(row);
+            }
+        }
+    }
+    rows
+}
+
+fn marker_line(line: &str, marker: &str) -> String {
+    let indent: String = line
+        .chars()
+        .take_while(|c| c == &' ' || c == &'\t')
+        .collect();
+    let eol = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+    format!("{}{}{}", indent, marker, eol)
+}
+
+#[cfg(test)]
+mod write {
+    use super::*;
+
+    #[test]
+    fn suffix() {
+        assert_eq!(
+            write(
+                ".card {\n  &__title {\n    color: red;\n  }\n}\n",
+                &Style::default(),
+                &Policy::default()
+            )
+            .unwrap(),
+            ".card {\n  // rta: .card__title\n  &__title {\n    color: red;\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn multi_selectors() {
+        assert_eq!(
+            write(
+                ".card {\n  &__title, &__subtitle {}\n}",
+                &Style::default(),
+                &Policy::default()
+            )
+            .unwrap(),
+            ".card {\n  // rta: .card__title, .card__subtitle\n  &__title, &__subtitle {}\n}"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            write(".a, .b {\n\t&-c {\n\t\t&-d {}\n\t\t.e {}\n\t}\n}\n", &Style::default(), &Policy::default()).unwrap(),
+            ".a, .b {\n\t// rta: .a-c, .b-c\n\t&-c {\n\t\t// rta: .a-c-d, .b-c-d\n\t\t&-d {}\n\t\t.e {}\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn not_evil() {
+        let input = ".a {\n  &:hover {}\n  & .b {}\n  .c & {}\n}\n";
+        assert_eq!(
+            write(input, &Style::default(), &Policy::default()).unwrap(),
+            input
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        let input = ".a {\r\n  &-b {}\r\n}\r\n";
+        let once = write(input, &Style::default(), &Policy::default()).unwrap();
+        assert_eq!(once, ".a {\r\n  // rta: .a-b\r\n  &-b {}\r\n}\r\n");
+        assert_eq!(
+            write(&once, &Style::default(), &Policy::default()).unwrap(),
+            once
+        );
+    }
+
+    #[test]
+    fn style() {
+        let style = Style {
+            prefix: "/* @see".to_string(),
+            format: "{prefix} {selectors} */".to_string(),
+        };
+        let written = write(".a {\n  &-b {}\n}\n", &style, &Policy::default()).unwrap();
+        assert_eq!(written, ".a {\n  /* @see .a-b */\n  &-b {}\n}\n");
+        assert_eq!(
+            write(&written, &style, &Policy::default()).unwrap(),
+            written
+        );
+        assert_eq!(reset(&written, &style), ".a {\n  &-b {}\n}\n");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let errors = write(".a {\n  &-b {}\n", &Style::default(), &Policy::default());
+        assert_eq!(errors.unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn replace_stale() {
+        assert_eq!(
+            write(
+                ".a {\n  // rta: .x-b\n  &-b {}\n}\n",
+                &Style::default(),
+                &Policy::default()
+            )
+            .unwrap(),
+            ".a {\n  // rta: .a-b\n  &-b {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn allowed() {
+        let policy = Policy {
+            allow: vec![crate::ambuster::Pattern::new("^&--").unwrap()],
+            deny: vec![],
+        };
+        assert_eq!(
+            write(".a {\n  &--b {}\n  &-c {}\n}\n", &Style::default(), &policy).unwrap(),
+            ".a {\n  &--b {}\n  // rta: .a-c\n  &-c {}\n}\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod reset {
+    use super::*;
+
+    #[test]
+    fn remove_markers() {
+        assert_eq!(
+            reset(
+                ".a {\n  // rta: .a-b\n  &-b {}\n  // rta: .a-c\n  &-c {}\n}\n",
+                &Style::default()
+            ),
+            ".a {\n  &-b {}\n  &-c {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn keep_other_comments() {
+        let input =
+            "// rta\n.a { // rta: .a\n  /*\n  // rta: .a-b\n  */\n  // rtb: .a-b\n  &-b {}\n}";
+        assert_eq!(reset(input, &Style::default()), input);
+    }
+
+    #[test]
+    fn block_comment_style() {
+        let style = Style {
+            prefix: "/* rta:".to_string(),
+            format: "{prefix} {selectors} */".to_string(),
+        };
+        let input = ".a {\n  &-b {\n    &-c {}\n  }\n  &-d {}\n}\n";
+        let written = write(input, &style, &Policy::default()).unwrap();
+        assert_eq!(
+            written,
+            ".a {\n  /* rta: .a-b */\n  &-b {\n    /* rta: .a-b-c */\n    &-c {}\n  }\n  /* rta: .a-d */\n  &-d {}\n}\n"
+        );
+        assert_eq!(parser::errors(&written), vec![]);
+        assert_eq!(reset(&written, &style), input);
+    }
+
+    #[test]
+    fn restore_written() {
+        let input = ".a {\r\n  // b\r\n  &-b {\r\n    &-c {}\r\n  }\r\n}";
+        let written = write(input, &Style::default(), &Policy::default()).unwrap();
+        assert_ne!(written, input);
+        assert_eq!(reset(&written, &Style::default()), input);
+        assert_eq!(reset(input, &Style::default()), input);
+    }
+}
+
+#[cfg(test)]
+mod update {
+    use super::*;
+
+    const INPUT: &str = ".card {\n  // rta: .block__title\n  &__title {}\n  // rta: .card__body\n  &__body {}\n  // rta: .card-gone\n  .gone {}\n}\n";
+
+    #[test]
+    fn stale_markers() {
+        assert_eq!(
+            stale(INPUT, &Style::default(), &Policy::default()),
+            vec![
+                Stale {
+                    row: 1,
+                    found: "// rta: .block__title".to_string(),
+                    expected: Some("// rta: .card__title".to_string()),
+                },
+                Stale {
+                    row: 5,
+                    found: "// rta: .card-gone".to_string(),
+                    expected: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rewrite_stale_only() {
+        assert_eq!(
+            update(INPUT, &Style::default(), &Policy::default()).unwrap(),
+            ".card {\n  // rta: .card__title\n  &__title {}\n  // rta: .card__body\n  &__body {}\n  .gone {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn missing_markers_are_not_added() {
+        let input = ".a {\n  &-b {}\n}\n";
+        assert_eq!(stale(input, &Style::default(), &Policy::default()), vec![]);
+        assert_eq!(
+            update(input, &Style::default(), &Policy::default()).unwrap(),
+            input
+        );
+    }
+}
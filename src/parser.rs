@@ -1,58 +1,176 @@
+use crate::expression::Comment;
+use crate::expression::Error;
 use crate::expression::Expr;
+use crate::expression::Forward;
 use crate::expression::Import;
-use crate::expression::Include;
+use crate::expression::ImportPath;
 use crate::expression::Property;
 use crate::expression::Scope;
+use crate::expression::Setting;
+use crate::expression::Use;
+use crate::expression::Word;
 use crate::lexer::Cursor;
 use crate::lexer::Lexer;
 use crate::lexer::PToken;
 use crate::lexer::Range;
 use crate::lexer::Token;
 
-struct Parser {
+// What made the input unparsable.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ErrorKind {
+    UnbalancedBrace,   // `}` without an open block
+    MissingColon,      // property without `:` after its name
+    UnterminatedBlock, // block or comment not closed before the end of the input
+    StrayToken,        // token which cannot start a rule or a property
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub range: Range,
+}
+
+pub struct Parser {
     lexer: Lexer,
     curr: Option<PToken>,
     peek: Option<PToken>,
+    last: Option<Cursor>, // end of the previous token
+    comments: Vec<Comment>,
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
-    fn new(mut lexer: Lexer) -> Parser {
-        let curr = lexer.token();
-        let peek = lexer.token();
-        Parser { lexer, curr, peek }
+    pub fn new(lexer: Lexer) -> Parser {
+        let mut parser = Parser {
+            lexer,
+            curr: None,
+            peek: None,
+            last: None,
+            comments: vec![],
+            errors: vec![],
+        };
+        parser.next();
+        parser.next();
+        parser
     }
 
     fn next(&mut self) {
-        self.curr = self.peek.clone();
+        if let Some(curr) = &self.curr {
+            self.last = Some(curr.range.to.clone());
+        }
+        self.curr = self.peek.take();
         self.peek = self.lexer.token();
+
+        if let Some(PToken {
+            token: Token::Comment(value),
+            range,
+        }) = &self.peek
+        {
+            if value.starts_with("/*") && (value.len() < 4 || !value.ends_with("*/")) {
+                self.error(
+                    ErrorKind::UnterminatedBlock,
+                    "comment is not closed".to_string(),
+                    Range::new(
+                        range.from.clone(),
+                        Cursor::new(range.from.row, range.from.column + 1),
+                    ),
+                );
+            }
+        }
     }
 
-    fn parse(&mut self) -> Vec<Expr> {
-        self.parse_expression()
+    fn error(&mut self, kind: ErrorKind, message: String, range: Range) {
+        self.errors.push(ParseError {
+            kind,
+            message,
+            range,
+        });
+    }
+
+    // Returns the rules and properties which could be parsed, with the
+    // errors of the rest.
+    pub fn parse(&mut self) -> (Vec<Expr>, Vec<ParseError>) {
+        let mut vec = self.parse_expression();
+
+        // only a `}` without an open block stops the top level early
+        while let Some(curr) = self.curr.clone() {
+            self.error(
+                ErrorKind::UnbalancedBrace,
+                "unexpected `}` without an open block".to_string(),
+                curr.range.clone(),
+            );
+            vec.push(Expr::Error(Error { range: curr.range }));
+            self.next();
+            vec.extend(self.parse_expression());
+        }
+
+        let mut errors = self.errors.split_off(0);
+        errors.sort_by(|a, b| a.range.from.cmp(&b.range.from));
+        (vec, errors)
     }
 
     fn parse_expression(&mut self) -> Vec<Expr> {
         let mut vec = vec![];
 
-        while self.curr.is_some() {
-            if self.curr.clone().unwrap().token == Token::RBrace {
-                break;
-            } else if self.is_property() {
-                match self.parse_property() {
-                    Some(p) => vec.push(Expr::Property(p)),
-                    _ => (),
+        while let Some(curr) = self.curr.clone() {
+            match curr.token {
+                Token::RBrace => break,
+                Token::Comment(value) => self.comments.push(Comment {
+                    range: curr.range,
+                    value,
+                }),
+                Token::Semicolon => (),
+                Token::Comma => {
+                    self.error(
+                        ErrorKind::StrayToken,
+                        "unexpected `,`".to_string(),
+                        curr.range.clone(),
+                    );
+                    vec.push(Expr::Error(Error { range: curr.range }));
+                }
+                Token::LBrace => {
+                    // the whole block is skipped
+                    self.error(
+                        ErrorKind::StrayToken,
+                        "unexpected `{` without a selector".to_string(),
+                        curr.range.clone(),
+                    );
+                    self.next();
+                    self.parse_expression();
+                    let to = match self.curr.clone() {
+                        Some(curr) => curr.range.to,
+                        None => self.last.clone().unwrap_or_else(|| curr.range.to.clone()),
+                    };
+                    vec.push(Expr::Error(Error {
+                        range: Range::new(curr.range.from, to),
+                    }));
                 }
-            } else if self.is_scope() {
-                match self.parse_scope() {
-                    Some(s) => {
-                        vec.push(Expr::Scope(s));
+                _ => {
+                    if self.is_property() {
+                        if let Some(p) = self.parse_property() {
+                            // the comments of a skipped statement stay where
+                            // they are rather than going to the next one
+                            if let Expr::Error(_) = p {
+                                vec.extend(self.comments.drain(..).map(Expr::Comment));
+                            }
+                            vec.push(p);
+                        }
+                        // the last property of a block may end with `}`
+                        if self.is_token(&Token::RBrace) {
+                            continue;
+                        }
+                    } else if self.is_scope() {
+                        if let Some(s) = self.parse_scope() {
+                            vec.push(Expr::Scope(s));
+                        }
                     }
-                    _ => (),
                 }
             }
             self.next();
         }
 
+        vec.extend(self.comments.drain(..).map(Expr::Comment));
         vec
     }
 
@@ -60,12 +178,15 @@ impl Parser {
         let mut curr = self.curr.clone();
         let mut selectors = vec![];
         let mut value = "".to_string();
+        let comments = self.comments.split_off(0);
 
         let from = self.curr.clone()?.range.from;
+        let mut open = from.clone();
 
         // parse selectors
         while curr.clone().is_some() {
-            match curr?.token {
+            let pt = curr?;
+            match pt.token {
                 Token::Value(val) => value = value + &val + " ",
                 Token::Comma => {
                     selectors.push(value.trim().to_string());
@@ -73,9 +194,11 @@ impl Parser {
                 }
                 Token::LBrace => {
                     selectors.push(value.trim().to_string());
+                    open = pt.range.to;
                     break;
                 }
                 Token::Colon => value = value.trim().to_string() + ":",
+                Token::Comment(_) => (),
                 _ => break,
             };
             self.next();
@@ -85,32 +208,215 @@ impl Parser {
         self.next();
         let children = self.parse_expression();
 
-        let to = self.curr.clone()?.range.from;
+        let to = match self.curr.clone() {
+            Some(curr) => curr.range.from,
+            None => {
+                self.error(
+                    ErrorKind::UnterminatedBlock,
+                    format!("block of `{}` is not closed", selectors.join(", ")),
+                    Range::new(from.clone(), open),
+                );
+                self.last.clone().unwrap_or_else(|| from.clone())
+            }
+        };
 
         Some(Scope {
             selectors,
+            comments,
             children,
             range: Range::new(from, to),
         })
     }
 
-    fn parse_property(&mut self) -> Option<Property> {
-        let from = self.curr.clone()?.range.from;
-        match self.curr.clone()?.token {
+    // Parses `key: value` up to the `;` or `}` ending it, or an at-rule
+    // statement such as `@include foo;` which has no `:`. A malformed one
+    // is skipped up to there.
+    fn parse_property(&mut self) -> Option<Expr> {
+        if let Some(Token::Value(keyword)) = self.curr.as_ref().map(|c| &c.token) {
+            match keyword.as_str() {
+                "@import" => return self.parse_import(),
+                "@use" | "@forward" => return self.parse_module(),
+                _ => (),
+            }
+        }
+        let curr = self.curr.clone()?;
+        let from = curr.range.from.clone();
+        match curr.token {
             Token::Value(key) => {
                 self.next();
-                self.next(); // skip ':'
+                if self.is_token(&Token::Colon) {
+                    self.next();
+                } else if !key.starts_with('@') {
+                    self.error(
+                        ErrorKind::MissingColon,
+                        format!("expected `:` after `{}`", key),
+                        curr.range,
+                    );
+                    return Some(self.skip_statement(from));
+                }
                 let value = self.parse_property_value()?;
-                let to = self.curr.clone()?.range.from;
+                let to = self.statement_end(&from);
                 let prop = Property {
                     key,
                     value,
+                    comments: self.comments.split_off(0),
                     range: Range::new(from, to),
                 };
-                Some(prop)
+                Some(Expr::Property(prop))
+            }
+            token => {
+                self.error(
+                    ErrorKind::StrayToken,
+                    format!("unexpected `{}`", text(&token)),
+                    curr.range,
+                );
+                self.next();
+                Some(self.skip_statement(from))
+            }
+        }
+    }
+
+    // Parses `@import "a", url(b.css) screen;` up to the `;` or `}` ending
+    // it. A comma starts another url unless it separates media queries.
+    fn parse_import(&mut self) -> Option<Expr> {
+        let keyword = self.curr.clone()?.range;
+        let from = keyword.from.clone();
+        self.next();
+
+        let mut paths: Vec<ImportPath> = vec![];
+        let mut expects_url = true;
+        let mut comma = None;
+        while let Some(curr) = self.curr.clone() {
+            match curr.token {
+                Token::Value(value) if expects_url => {
+                    paths.push(ImportPath {
+                        range: curr.range,
+                        path: unquote(&value),
+                        media: None,
+                        css: is_plain_css(&value),
+                    });
+                    expects_url = false;
+                }
+                Token::Value(value) => {
+                    if let Some(p) = paths.last_mut() {
+                        let media = p.media.get_or_insert_with(String::new);
+                        media.push_str(&value);
+                        media.push(' ');
+                    }
+                }
+                Token::Colon | Token::Comma => {
+                    let next_url = matches!(
+                        &self.peek,
+                        Some(PToken { token: Token::Value(v), .. }) if is_url(v)
+                    );
+                    match paths.last_mut().and_then(|p| p.media.as_mut()) {
+                        Some(media) if curr.token == Token::Colon || !next_url => {
+                            let sep = if curr.token == Token::Colon {
+                                ": "
+                            } else {
+                                ", "
+                            };
+                            *media = media.trim_end().to_string() + sep;
+                        }
+                        _ => {
+                            expects_url = true;
+                            comma = Some(curr.range);
+                        }
+                    }
+                }
+                Token::Comment(_) => (),
+                _ => break,
             }
-            _ => None,
+            self.next();
+        }
+
+        let to = self.statement_end(&from);
+        if expects_url {
+            let (message, at) = match comma {
+                Some(comma) if !paths.is_empty() => ("expected a url after `,`", comma),
+                _ => ("expected a url after `@import`", keyword),
+            };
+            self.error(ErrorKind::StrayToken, message.to_string(), at);
+            return Some(Expr::Error(Error {
+                range: Range::new(from, to),
+            }));
         }
+
+        for p in &mut paths {
+            if let Some(media) = p.media.take() {
+                p.media = Some(media.trim().to_string());
+                p.css = true;
+            }
+        }
+        Some(Expr::Import(Import {
+            range: Range::new(from, to),
+            paths,
+            comments: self.comments.split_off(0),
+        }))
+    }
+
+    // Parses `@use` and `@forward` rules up to the `;` or `}` ending them.
+    // A malformed one is left as an error.
+    fn parse_module(&mut self) -> Option<Expr> {
+        let curr = self.curr.clone()?;
+        let keyword = Word {
+            value: text(&curr.token).to_string(),
+            range: curr.range,
+        };
+        let from = keyword.range.from.clone();
+        self.next();
+
+        let words = self.words();
+        let range = Range::new(from.clone(), self.statement_end(&from));
+        let rule = match module_rule(&keyword, words) {
+            Ok(rule) => rule,
+            Err((message, at)) => {
+                self.error(ErrorKind::StrayToken, message, at);
+                return Some(Expr::Error(Error { range }));
+            }
+        };
+        let comments = self.comments.split_off(0);
+        Some(if keyword.value == "@use" {
+            Expr::Use(Use {
+                range,
+                url: rule.url,
+                namespace: rule.name,
+                with: rule.with,
+                comments,
+            })
+        } else {
+            Expr::Forward(Forward {
+                range,
+                url: rule.url,
+                prefix: rule.name.map(|mut n| {
+                    n.value.pop(); // `*`
+                    n
+                }),
+                show: rule.show,
+                hide: rule.hide,
+                with: rule.with,
+                comments,
+            })
+        })
+    }
+
+    // Tokens up to the end of the statement, with the parentheses split
+    // from the values around them.
+    fn words(&mut self) -> Vec<Word> {
+        let mut vec = vec![];
+        while let Some(curr) = self.curr.clone() {
+            match curr.token {
+                Token::Value(value) => split_parens(&value, curr.range, &mut vec),
+                Token::Colon | Token::Comma => vec.push(Word {
+                    value: text(&curr.token).to_string(),
+                    range: curr.range,
+                }),
+                Token::Comment(_) => (),
+                _ => break,
+            }
+            self.next();
+        }
+        vec
     }
 
     fn parse_property_value(&mut self) -> Option<String> {
@@ -120,6 +426,9 @@ impl Parser {
         while curr.is_some() {
             match curr?.token {
                 Token::Value(val) => value = value + &val + " ",
+                Token::Colon => value = value.trim_end().to_string() + ": ",
+                Token::Comma => value = value.trim_end().to_string() + ", ",
+                Token::Comment(_) => (),
                 Token::Semicolon => break,
                 Token::RBrace => break,
                 _ => break,
@@ -131,20 +440,52 @@ impl Parser {
         Some(value.trim().to_string())
     }
 
+    // Moves to the `;` or `}` ending the statement started at `from`,
+    // whose tokens are left as an error.
+    fn skip_statement(&mut self, from: Cursor) -> Expr {
+        while let Some(curr) = &self.curr {
+            if matches!(curr.token, Token::Semicolon | Token::RBrace) {
+                break;
+            }
+            self.next();
+        }
+        let to = self.last.clone().unwrap_or_else(|| from.clone());
+        Expr::Error(Error {
+            range: Range::new(from, to),
+        })
+    }
+
+    // Where the statement started at `from` ends, at its `;` if any.
+    fn statement_end(&self, from: &Cursor) -> Cursor {
+        match &self.curr {
+            Some(PToken {
+                token: Token::Semicolon,
+                range,
+            }) => range.from.clone(),
+            _ => self.last.clone().unwrap_or_else(|| from.clone()),
+        }
+    }
+
+    fn is_token(&self, token: &Token) -> bool {
+        self.curr.as_ref().is_some_and(|c| &c.token == token)
+    }
+
+    // Whether the statement ends with `;`, `}` or the end of the input
+    // rather than opening a block.
     fn is_property(&mut self) -> bool {
         let mut lexer = self.lexer.clone();
 
         let mut pt = self.peek.clone();
         while pt.is_some() {
             match pt.unwrap().token {
-                Token::Semicolon => return true,
+                Token::Semicolon | Token::RBrace => return true,
                 Token::LBrace => return false,
                 _ => (),
             };
             pt = lexer.token();
         }
 
-        false
+        true
     }
 
     fn is_scope(&mut self) -> bool {
@@ -162,31 +503,266 @@ impl Parser {
 
         false
     }
+}
 
-    fn next_not_value_token(&mut self) -> Option<Token> {
-        let mut lexer = self.lexer.clone();
+// Errors of the input, for reporting them without using the rules.
+pub fn errors(input: &str) -> Vec<ParseError> {
+    Parser::new(Lexer::new(input.chars().collect())).parse().1
+}
 
-        let mut pt = self.peek.clone();
-        while pt.is_some() {
-            match pt?.token {
-                Token::Value(_) => (),
-                t => return Some(t),
-            };
-            pt = lexer.token();
+// The rules and properties of the input, unless it has parse errors.
+pub fn parse(input: &str) -> Result<Vec<Expr>, Vec<ParseError>> {
+    match Parser::new(Lexer::new(input.chars().collect())).parse() {
+        (exprs, errors) if errors.is_empty() => Ok(exprs),
+        (_, errors) => Err(errors),
+    }
+}
+
+// Parts of a `@use` or `@forward` rule following its keyword.
+struct ModuleRule {
+    url: Word,
+    name: Option<Word>,
+    show: Vec<Word>,
+    hide: Vec<Word>,
+    with: Vec<Setting>,
+}
+
+fn module_rule(keyword: &Word, words: Vec<Word>) -> Result<ModuleRule, (String, Range)> {
+    let mut words = words.into_iter().peekable();
+    let url = match words.next() {
+        Some(w) if w.value.starts_with('"') || w.value.starts_with('\'') => Word {
+            value: unquote(&w.value),
+            range: w.range,
+        },
+        Some(w) => {
+            return Err((
+                format!("expected a quoted url, found `{}`", w.value),
+                w.range,
+            ))
         }
+        None => {
+            let message = format!("expected a url after `{}`", keyword.value);
+            return Err((message, keyword.range.clone()));
+        }
+    };
+    let mut rule = ModuleRule {
+        url,
+        name: None,
+        show: vec![],
+        hide: vec![],
+        with: vec![],
+    };
 
-        None
+    while let Some(word) = words.next() {
+        match word.value.as_str() {
+            "as" => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| ("expected a name after `as`".to_string(), word.range))?;
+                if keyword.value == "@forward" && !name.value.ends_with('*') {
+                    let message =
+                        format!("expected a prefix ending with `*`, found `{}`", name.value);
+                    return Err((message, name.range));
+                }
+                rule.name = Some(name);
+            }
+            "show" | "hide" if keyword.value == "@forward" => {
+                let mut names = vec![];
+                while let Some(w) =
+                    words.next_if(|w| !matches!(w.value.as_str(), "as" | "show" | "hide" | "with"))
+                {
+                    if w.value != "," {
+                        names.push(w);
+                    }
+                }
+                if word.value == "show" {
+                    rule.show = names;
+                } else {
+                    rule.hide = names;
+                }
+            }
+            "with" => rule.with = settings(&mut words, word.range)?,
+            _ => return Err((format!("unexpected `{}`", word.value), word.range)),
+        }
+    }
+    Ok(rule)
+}
+
+// Reads `($a: 1, $b: (c: d))` following `with`.
+fn settings(
+    words: &mut impl Iterator<Item = Word>,
+    with: Range,
+) -> Result<Vec<Setting>, (String, Range)> {
+    let open = match words.next() {
+        Some(w) if w.value == "(" => w.range,
+        Some(w) => {
+            return Err((
+                format!("expected `(` after `with`, found `{}`", w.value),
+                w.range,
+            ))
+        }
+        None => return Err(("expected `(` after `with`".to_string(), with)),
+    };
+
+    let mut entries: Vec<Vec<Word>> = vec![vec![]];
+    let mut depth = 0;
+    let mut closed = false;
+    for w in words.by_ref() {
+        match w.value.as_str() {
+            ")" if depth == 0 => {
+                closed = true;
+                break;
+            }
+            "," if depth == 0 => {
+                entries.push(vec![]);
+                continue;
+            }
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => (),
+        }
+        entries.last_mut().unwrap().push(w);
+    }
+    if !closed {
+        return Err(("`(` after `with` is not closed".to_string(), open));
+    }
+
+    let mut vec = vec![];
+    for entry in entries.into_iter().filter(|e| !e.is_empty()) {
+        match entry.as_slice() {
+            [name, colon, value @ ..]
+                if name.value.starts_with('$') && colon.value == ":" && !value.is_empty() =>
+            {
+                let last = &value[value.len() - 1];
+                vec.push(Setting {
+                    range: Range::new(name.range.from.clone(), last.range.to.clone()),
+                    name: name.clone(),
+                    value: Word {
+                        value: join(value),
+                        range: Range::new(value[0].range.from.clone(), last.range.to.clone()),
+                    },
+                });
+            }
+            _ => {
+                let message = format!("expected `$name: value`, found `{}`", join(&entry));
+                return Err((message, entry[0].range.clone()));
+            }
+        }
+    }
+    Ok(vec)
+}
+
+// Splits `with($a` and `d))` around their parentheses, except in strings.
+fn split_parens(value: &str, range: Range, vec: &mut Vec<Word>) {
+    if value.contains('\n') {
+        vec.push(Word {
+            value: value.to_string(),
+            range,
+        });
+        return;
+    }
+    let row = range.from.row;
+    let word = |value: String, from: usize, to: usize| Word {
+        value,
+        range: Range::new(Cursor::new(row, from), Cursor::new(row, to)),
+    };
+
+    let mut text = String::new();
+    let mut start = range.from.column;
+    let mut quote = None;
+    for (i, c) in value.chars().enumerate() {
+        let column = range.from.column + i;
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '(' || c == ')' => {
+                if !text.is_empty() {
+                    vec.push(word(text.split_off(0), start, column - 1));
+                }
+                vec.push(word(c.to_string(), column, column));
+                continue;
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => (),
+        }
+        if text.is_empty() {
+            start = column;
+        }
+        text.push(c);
+    }
+    if !text.is_empty() {
+        vec.push(word(text, start, range.to.column));
+    }
+}
+
+// Text of the words, separated by a space where they were apart.
+fn join(words: &[Word]) -> String {
+    let mut text = String::new();
+    for (i, w) in words.iter().enumerate() {
+        if i > 0 {
+            let prev = &words[i - 1].range.to;
+            let from = &w.range.from;
+            if prev.row != from.row || prev.column + 1 != from.column {
+                text.push(' ');
+            }
+        }
+        text.push_str(&w.value);
+    }
+    text
+}
+
+// Whether the value is a quoted string or `url()`, as opposed to a media
+// query.
+fn is_url(value: &str) -> bool {
+    value.starts_with('"') || value.starts_with('\'') || value.starts_with("url(")
+}
+
+// Imports which Sass leaves as they are rather than loading the file.
+fn is_plain_css(value: &str) -> bool {
+    let path = unquote(value);
+    value.starts_with("url(")
+        || path.ends_with(".css")
+        || path.starts_with("http://")
+        || path.starts_with("https://")
+        || path.starts_with("//")
+}
+
+fn unquote(value: &str) -> String {
+    let value = value
+        .strip_prefix("url(")
+        .and_then(|v| v.strip_suffix(')'))
+        .map_or(value, str::trim);
+    for quote in &["\"", "'"] {
+        if let Some(v) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return v.to_string();
+        }
+    }
+    value.to_string()
+}
+
+fn text(token: &Token) -> &str {
+    match token {
+        Token::Value(v) | Token::Comment(v) => v,
+        Token::Comma => ",",
+        Token::LBrace => "{",
+        Token::RBrace => "}",
+        Token::Colon => ":",
+        Token::Semicolon => ";",
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod parser {
     use super::*;
 
     fn do_parser(input: &str, expect: Vec<Expr>) {
         let lexer = Lexer::new(input.chars().collect());
         let mut parser = Parser::new(lexer);
-        assert_eq!(parser.parse(), expect);
+        assert_eq!(parser.parse(), (expect, vec![]));
     }
 
     #[test]
@@ -197,16 +773,19 @@ mod parser {
                 Expr::Property(Property {
                     key: "color".to_string(),
                     value: "red".to_string(),
+                    comments: vec![],
                     range: Range::new(Cursor::new(0, 0), Cursor::new(0, 10)),
                 }),
                 Expr::Property(Property {
                     key: "padding".to_string(),
                     value: "1px 1rem".to_string(),
+                    comments: vec![],
                     range: Range::new(Cursor::new(1, 0), Cursor::new(1, 17)),
                 }),
                 Expr::Property(Property {
                     key: "margin".to_string(),
                     value: "0 1px 2px".to_string(),
+                    comments: vec![],
                     range: Range::new(Cursor::new(1, 19), Cursor::new(1, 36)),
                 }),
             ],
@@ -220,6 +799,7 @@ mod parser {
             vec![Expr::Property(Property {
                 key: "$primary".to_string(),
                 value: "#123456".to_string(),
+                comments: vec![],
                 range: Range::new(Cursor::new(0, 0), Cursor::new(0, 17)),
             })],
         );
@@ -236,11 +816,13 @@ mod parser {
                 vec![
                     Expr::Scope(Scope {
                         selectors: vec![".a".to_string()],
+                        comments: vec![],
                         children: vec![],
                         range: Range::new(Cursor::new(0, 0), Cursor::new(0, 4)),
                     }),
                     Expr::Scope(Scope {
                         selectors: vec![".c".to_string()],
+                        comments: vec![],
                         children: vec![],
                         range: Range::new(Cursor::new(1, 0), Cursor::new(1, 4)),
                     }),
@@ -255,11 +837,13 @@ mod parser {
                 vec![
                     Expr::Scope(Scope {
                         selectors: vec![".a .b".to_string()],
+                        comments: vec![],
                         children: vec![],
                         range: Range::new(Cursor::new(0, 0), Cursor::new(0, 7)),
                     }),
                     Expr::Scope(Scope {
                         selectors: vec![".c".to_string(), ".d".to_string()],
+                        comments: vec![],
                         children: vec![],
                         range: Range::new(Cursor::new(1, 0), Cursor::new(1, 8)),
                     }),
@@ -274,11 +858,13 @@ mod parser {
                 vec![
                     Expr::Scope(Scope {
                         selectors: vec![".a:b".to_string()],
+                        comments: vec![],
                         children: vec![],
                         range: Range::new(Cursor::new(0, 0), Cursor::new(0, 6)),
                     }),
                     Expr::Scope(Scope {
                         selectors: vec![".cc::ff".to_string()],
+                        comments: vec![],
                         children: vec![],
                         range: Range::new(Cursor::new(0, 8), Cursor::new(0, 17)),
                     }),
@@ -292,14 +878,17 @@ mod parser {
                 ".a .b { .c, .d {} #e {} }",
                 vec![Expr::Scope(Scope {
                     selectors: vec![".a .b".to_string()],
+                    comments: vec![],
                     children: vec![
                         Expr::Scope(Scope {
                             selectors: vec![".c".to_string(), ".d".to_string()],
+                            comments: vec![],
                             children: vec![],
                             range: Range::new(Cursor::new(0, 8), Cursor::new(0, 16)),
                         }),
                         Expr::Scope(Scope {
                             selectors: vec!["#e".to_string()],
+                            comments: vec![],
                             children: vec![],
                             range: Range::new(Cursor::new(0, 18), Cursor::new(0, 22)),
                         }),
@@ -315,17 +904,21 @@ mod parser {
                 ".a { color: red; .b { width: 100px; } }",
                 vec![Expr::Scope(Scope {
                     selectors: vec![".a".to_string()],
+                    comments: vec![],
                     children: vec![
                         Expr::Property(Property {
                             key: "color".to_string(),
                             value: "red".to_string(),
+                            comments: vec![],
                             range: Range::new(Cursor::new(0, 5), Cursor::new(0, 15)),
                         }),
                         Expr::Scope(Scope {
                             selectors: vec![".b".to_string()],
+                            comments: vec![],
                             children: vec![Expr::Property(Property {
                                 key: "width".to_string(),
                                 value: "100px".to_string(),
+                                comments: vec![],
                                 range: Range::new(Cursor::new(0, 22), Cursor::new(0, 34)),
                             })],
                             range: Range::new(Cursor::new(0, 17), Cursor::new(0, 36)),
@@ -336,4 +929,485 @@ mod parser {
             );
         }
     }
+
+    #[test]
+    fn comments() {
+        do_parser(
+            "// a\n.a { /* b */ color: red; // c\n }\n// d",
+            vec![
+                Expr::Scope(Scope {
+                    selectors: vec![".a".to_string()],
+                    comments: vec![Comment {
+                        value: "// a".to_string(),
+                        range: Range::new(Cursor::new(0, 0), Cursor::new(0, 3)),
+                    }],
+                    children: vec![
+                        Expr::Property(Property {
+                            key: "color".to_string(),
+                            value: "red".to_string(),
+                            comments: vec![Comment {
+                                value: "/* b */".to_string(),
+                                range: Range::new(Cursor::new(1, 5), Cursor::new(1, 11)),
+                            }],
+                            range: Range::new(Cursor::new(1, 13), Cursor::new(1, 23)),
+                        }),
+                        Expr::Comment(Comment {
+                            value: "// c".to_string(),
+                            range: Range::new(Cursor::new(1, 25), Cursor::new(1, 28)),
+                        }),
+                    ],
+                    range: Range::new(Cursor::new(1, 0), Cursor::new(2, 1)),
+                }),
+                Expr::Comment(Comment {
+                    value: "// d".to_string(),
+                    range: Range::new(Cursor::new(3, 0), Cursor::new(3, 3)),
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn comments_in_selectors() {
+        do_parser(
+            ".a, /* b */ .c {}",
+            vec![Expr::Scope(Scope {
+                selectors: vec![".a".to_string(), ".c".to_string()],
+                comments: vec![],
+                children: vec![],
+                range: Range::new(Cursor::new(0, 0), Cursor::new(0, 16)),
+            })],
+        );
+    }
+
+    #[test]
+    fn statements() {
+        do_parser(
+            "@include a(1, 2);\n.a { font: 1px a, b; $m: (c: d) }",
+            vec![
+                Expr::Property(Property {
+                    key: "@include".to_string(),
+                    value: "a(1, 2)".to_string(),
+                    comments: vec![],
+                    range: Range::new(Cursor::new(0, 0), Cursor::new(0, 16)),
+                }),
+                Expr::Scope(Scope {
+                    selectors: vec![".a".to_string()],
+                    comments: vec![],
+                    children: vec![
+                        Expr::Property(Property {
+                            key: "font".to_string(),
+                            value: "1px a, b".to_string(),
+                            comments: vec![],
+                            range: Range::new(Cursor::new(1, 5), Cursor::new(1, 19)),
+                        }),
+                        Expr::Property(Property {
+                            key: "$m".to_string(),
+                            value: "(c: d)".to_string(),
+                            comments: vec![],
+                            range: Range::new(Cursor::new(1, 21), Cursor::new(1, 30)),
+                        }),
+                    ],
+                    range: Range::new(Cursor::new(1, 0), Cursor::new(1, 32)),
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn import() {
+        let path = |from: (usize, usize), to: (usize, usize), path: &str, media: Option<&str>| {
+            ImportPath {
+                range: Range::new(Cursor::new(from.0, from.1), Cursor::new(to.0, to.1)),
+                path: path.to_string(),
+                media: media.map(str::to_string),
+                css: media.is_some() || path.ends_with(".css"),
+            }
+        };
+        do_parser(
+            "// a\n@import \"b\", 'c/d';\n.e { @import url(f.css) screen and (orientation: landscape), print, url(\"g.css\") }",
+            vec![
+                Expr::Import(Import {
+                    range: Range::new(Cursor::new(1, 0), Cursor::new(1, 18)),
+                    paths: vec![
+                        path((1, 8), (1, 10), "b", None),
+                        path((1, 13), (1, 17), "c/d", None),
+                    ],
+                    comments: vec![Comment {
+                        value: "// a".to_string(),
+                        range: Range::new(Cursor::new(0, 0), Cursor::new(0, 3)),
+                    }],
+                }),
+                Expr::Scope(Scope {
+                    selectors: vec![".e".to_string()],
+                    comments: vec![],
+                    children: vec![Expr::Import(Import {
+                        range: Range::new(Cursor::new(2, 5), Cursor::new(2, 79)),
+                        paths: vec![
+                            path(
+                                (2, 13),
+                                (2, 22),
+                                "f.css",
+                                Some("screen and (orientation: landscape), print"),
+                            ),
+                            path((2, 68), (2, 79), "g.css", None),
+                        ],
+                        comments: vec![],
+                    })],
+                    range: Range::new(Cursor::new(2, 0), Cursor::new(2, 81)),
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn import_plain_css() {
+        assert!(is_plain_css("url(a)"));
+        assert!(is_plain_css("'https://a/b'"));
+        assert!(is_plain_css("\"//a/b\""));
+        assert!(!is_plain_css("\"a/css\""));
+        assert_eq!(unquote("url( 'a b' )"), "a b");
+    }
+
+    fn word(row: usize, from: usize, to: usize, value: &str) -> Word {
+        Word {
+            range: Range::new(Cursor::new(row, from), Cursor::new(row, to)),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn use_rule() {
+        do_parser(
+            "@use \"sass:math\";\n@use 'library' as * with ($black: #222, $map: (a: b));",
+            vec![
+                Expr::Use(Use {
+                    range: Range::new(Cursor::new(0, 0), Cursor::new(0, 16)),
+                    url: word(0, 5, 15, "sass:math"),
+                    namespace: None,
+                    with: vec![],
+                    comments: vec![],
+                }),
+                Expr::Use(Use {
+                    range: Range::new(Cursor::new(1, 0), Cursor::new(1, 53)),
+                    url: word(1, 5, 13, "library"),
+                    namespace: Some(word(1, 18, 18, "*")),
+                    with: vec![
+                        Setting {
+                            range: Range::new(Cursor::new(1, 26), Cursor::new(1, 37)),
+                            name: word(1, 26, 31, "$black"),
+                            value: word(1, 34, 37, "#222"),
+                        },
+                        Setting {
+                            range: Range::new(Cursor::new(1, 40), Cursor::new(1, 51)),
+                            name: word(1, 40, 43, "$map"),
+                            value: word(1, 46, 51, "(a: b)"),
+                        },
+                    ],
+                    comments: vec![],
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn forward_rule() {
+        do_parser(
+            "@forward \"src/list\" as list-* hide list-reset, $gap;\n@forward \"lib\" show a with($c: fn(1, 2) !default,);",
+            vec![
+                Expr::Forward(Forward {
+                    range: Range::new(Cursor::new(0, 0), Cursor::new(0, 51)),
+                    url: word(0, 9, 18, "src/list"),
+                    prefix: Some(word(0, 23, 28, "list-")),
+                    show: vec![],
+                    hide: vec![word(0, 35, 44, "list-reset"), word(0, 47, 50, "$gap")],
+                    with: vec![],
+                    comments: vec![],
+                }),
+                Expr::Forward(Forward {
+                    range: Range::new(Cursor::new(1, 0), Cursor::new(1, 50)),
+                    url: word(1, 9, 13, "lib"),
+                    prefix: None,
+                    show: vec![word(1, 20, 20, "a")],
+                    hide: vec![],
+                    with: vec![Setting {
+                        range: Range::new(Cursor::new(1, 27), Cursor::new(1, 47)),
+                        name: word(1, 27, 28, "$c"),
+                        value: word(1, 31, 47, "fn(1, 2) !default"),
+                    }],
+                    comments: vec![],
+                }),
+            ],
+        );
+    }
+
+    #[cfg(test)]
+    mod errors {
+        use super::*;
+
+        fn do_errors(input: &str, expect: Vec<(ErrorKind, &str, Range)>) {
+            let errors: Vec<(ErrorKind, String, Range)> = super::super::errors(input)
+                .into_iter()
+                .map(|e| (e.kind, e.message, e.range))
+                .collect();
+            let expect: Vec<(ErrorKind, String, Range)> = expect
+                .into_iter()
+                .map(|(k, m, r)| (k, m.to_string(), r))
+                .collect();
+            assert_eq!(errors, expect);
+        }
+
+        fn do_parser_with_errors(input: &str, expect: Vec<Expr>, errors: usize) {
+            let lexer = Lexer::new(input.chars().collect());
+            let (exprs, e) = Parser::new(lexer).parse();
+            assert_eq!(exprs, expect);
+            assert_eq!(e.len(), errors);
+        }
+
+        #[test]
+        fn unbalanced_brace() {
+            do_errors(
+                ".a {}\n}\n.b {}",
+                vec![(
+                    ErrorKind::UnbalancedBrace,
+                    "unexpected `}` without an open block",
+                    Range::new(Cursor::new(1, 0), Cursor::new(1, 0)),
+                )],
+            );
+            let (exprs, _) = Parser::new(Lexer::new(".a {}\n}\n.b {}".chars().collect())).parse();
+            assert_eq!(exprs.len(), 3);
+        }
+
+        #[test]
+        fn missing_colon() {
+            do_errors(
+                ".a { color red; width: 1px; }",
+                vec![(
+                    ErrorKind::MissingColon,
+                    "expected `:` after `color`",
+                    Range::new(Cursor::new(0, 5), Cursor::new(0, 9)),
+                )],
+            );
+        }
+
+        #[test]
+        fn unterminated_block() {
+            do_errors(
+                ".a {\n  .b { color: red; }\n",
+                vec![(
+                    ErrorKind::UnterminatedBlock,
+                    "block of `.a` is not closed",
+                    Range::new(Cursor::new(0, 0), Cursor::new(0, 3)),
+                )],
+            );
+        }
+
+        #[test]
+        fn stray_token() {
+            do_errors(
+                ", .a {}\n{ color: red; }\n:",
+                vec![
+                    (
+                        ErrorKind::StrayToken,
+                        "unexpected `,`",
+                        Range::new(Cursor::new(0, 0), Cursor::new(0, 0)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "unexpected `{` without a selector",
+                        Range::new(Cursor::new(1, 0), Cursor::new(1, 0)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "unexpected `:`",
+                        Range::new(Cursor::new(2, 0), Cursor::new(2, 0)),
+                    ),
+                ],
+            );
+        }
+
+        #[test]
+        fn module_rules() {
+            do_errors(
+                "@use foo;\n@forward \"a\" as b;\n@use \"a\" with ($x 1);\n@use;\n@use \"a\" with (;\n@use \"a\" with ($a: (b: c)",
+                vec![
+                    (
+                        ErrorKind::StrayToken,
+                        "expected a quoted url, found `foo`",
+                        Range::new(Cursor::new(0, 5), Cursor::new(0, 7)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "expected a prefix ending with `*`, found `b`",
+                        Range::new(Cursor::new(1, 16), Cursor::new(1, 16)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "expected `$name: value`, found `$x 1`",
+                        Range::new(Cursor::new(2, 15), Cursor::new(2, 16)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "expected a url after `@use`",
+                        Range::new(Cursor::new(3, 0), Cursor::new(3, 3)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "`(` after `with` is not closed",
+                        Range::new(Cursor::new(4, 14), Cursor::new(4, 14)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "`(` after `with` is not closed",
+                        Range::new(Cursor::new(5, 14), Cursor::new(5, 14)),
+                    ),
+                ],
+            );
+            let (exprs, _) = Parser::new(Lexer::new("@use foo;".chars().collect())).parse();
+            assert_eq!(
+                exprs,
+                vec![Expr::Error(Error {
+                    range: Range::new(Cursor::new(0, 0), Cursor::new(0, 8)),
+                })]
+            );
+        }
+
+        #[test]
+        fn import_rules() {
+            do_errors(
+                "@import;\n@import ,;\n@import \"a\",;",
+                vec![
+                    (
+                        ErrorKind::StrayToken,
+                        "expected a url after `@import`",
+                        Range::new(Cursor::new(0, 0), Cursor::new(0, 6)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "expected a url after `@import`",
+                        Range::new(Cursor::new(1, 0), Cursor::new(1, 6)),
+                    ),
+                    (
+                        ErrorKind::StrayToken,
+                        "expected a url after `,`",
+                        Range::new(Cursor::new(2, 11), Cursor::new(2, 11)),
+                    ),
+                ],
+            );
+            let (exprs, _) = Parser::new(Lexer::new("@import;".chars().collect())).parse();
+            assert_eq!(
+                exprs,
+                vec![Expr::Error(Error {
+                    range: Range::new(Cursor::new(0, 0), Cursor::new(0, 7)),
+                })]
+            );
+        }
+
+        #[test]
+        fn unterminated_comment() {
+            do_errors(
+                ".a {}\n.b { /* c }\n",
+                vec![
+                    (
+                        ErrorKind::UnterminatedBlock,
+                        "block of `.b` is not closed",
+                        Range::new(Cursor::new(1, 0), Cursor::new(1, 3)),
+                    ),
+                    (
+                        ErrorKind::UnterminatedBlock,
+                        "comment is not closed",
+                        Range::new(Cursor::new(1, 5), Cursor::new(1, 6)),
+                    ),
+                ],
+            );
+            do_errors(
+                "/*/",
+                vec![(
+                    ErrorKind::UnterminatedBlock,
+                    "comment is not closed",
+                    Range::new(Cursor::new(0, 0), Cursor::new(0, 1)),
+                )],
+            );
+        }
+
+        #[test]
+        fn comments_of_skipped_statements() {
+            let input = ".a {\n  // a\n  b c;\n  // d\n  @use;\n  e: f;\n}";
+            let (exprs, errors) = Parser::new(Lexer::new(input.chars().collect())).parse();
+            assert_eq!(errors.len(), 2);
+            let children = match &exprs[..] {
+                [Expr::Scope(s)] => &s.children,
+                _ => panic!("{:?}", exprs),
+            };
+            let comment = |row, value: &str| {
+                Expr::Comment(Comment {
+                    range: Range::new(Cursor::new(row, 2), Cursor::new(row, 5)),
+                    value: value.to_string(),
+                })
+            };
+            assert_eq!(children[0], comment(1, "// a"));
+            assert!(matches!(children[1], Expr::Error(_)));
+            assert_eq!(children[2], comment(3, "// d"));
+            assert!(matches!(children[3], Expr::Error(_)));
+            assert!(matches!(&children[4], Expr::Property(p) if p.comments.is_empty()));
+        }
+
+        #[test]
+        fn valid() {
+            do_errors(
+                ":root { --a: 1; }\n.a-#{$b} { &:hover { x: url(http://c/d); } ;; }",
+                vec![],
+            );
+        }
+
+        #[test]
+        fn recovery() {
+            do_parser_with_errors(
+                ".a { color red; width: 1px }\n} , { b: c; }",
+                vec![
+                    Expr::Scope(Scope {
+                        selectors: vec![".a".to_string()],
+                        comments: vec![],
+                        children: vec![
+                            Expr::Error(Error {
+                                range: Range::new(Cursor::new(0, 5), Cursor::new(0, 13)),
+                            }),
+                            Expr::Property(Property {
+                                key: "width".to_string(),
+                                value: "1px".to_string(),
+                                comments: vec![],
+                                range: Range::new(Cursor::new(0, 16), Cursor::new(0, 25)),
+                            }),
+                        ],
+                        range: Range::new(Cursor::new(0, 0), Cursor::new(0, 27)),
+                    }),
+                    Expr::Error(Error {
+                        range: Range::new(Cursor::new(1, 0), Cursor::new(1, 0)),
+                    }),
+                    Expr::Error(Error {
+                        range: Range::new(Cursor::new(1, 2), Cursor::new(1, 2)),
+                    }),
+                    Expr::Error(Error {
+                        range: Range::new(Cursor::new(1, 4), Cursor::new(1, 12)),
+                    }),
+                ],
+                4,
+            );
+        }
+
+        #[test]
+        fn recovery_at_end() {
+            do_parser_with_errors(
+                ".a { b",
+                vec![Expr::Scope(Scope {
+                    selectors: vec![".a".to_string()],
+                    comments: vec![],
+                    children: vec![Expr::Error(Error {
+                        range: Range::new(Cursor::new(0, 5), Cursor::new(0, 5)),
+                    })],
+                    range: Range::new(Cursor::new(0, 0), Cursor::new(0, 5)),
+                })],
+                2,
+            );
+        }
+    }
 }
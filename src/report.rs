@@ -0,0 +1,232 @@
+use crate::baseline;
+use crate::checker;
+use crate::checker::Finding;
+use crate::config::Severity;
+use crate::lexer::Range;
+
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Record {
+    pub file: String,
+    pub rule: String,
+    pub severity: Severity,
+    pub message: String,
+    pub range: Range,
+    pub selector: String,
+    pub resolved: Vec<String>,
+    pub fixable: bool,
+    pub policy: Option<String>,
+    pub fingerprint: String,
+}
+
+impl Record {
+    pub fn new(file: &str, input: &str, finding: &Finding, severity: Severity) -> Record {
+        Record {
+            file: file.to_string(),
+            rule: finding.rule.to_string(),
+            severity,
+            message: finding.message(),
+            range: finding.range.clone(),
+            selector: finding.selector.clone(),
+            resolved: finding.resolved.clone(),
+            fixable: finding.fixable,
+            policy: finding.policy.clone(),
+            fingerprint: baseline::fingerprint(file, input, finding),
+        }
+    }
+}
+
+// One `path:line:col: message` line per record, positions are 1-based.
+pub fn text(records: &[Record]) -> String {
+    records
+        .iter()
+        .map(|r| {
+            format!(
+                "{}:{}:{}: {}\n",
+                r.file,
+                r.range.from.row + 1,
+                r.range.from.column + 1,
+                r.message
+            )
+        })
+        .collect()
+}
+
+pub fn json(records: &[Record]) -> String {
+    serde_json::to_string_pretty(records).unwrap() + "\n"
+}
+
+// SARIF 2.1.0 log with a single run, columns of regions are 1-based code
+// points and their ends are exclusive.
+pub fn sarif(records: &[Record]) -> String {
+    let rules: Vec<serde_json::Value> = checker::RULES
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.id,
+                "name": r.name,
+                "shortDescription": { "text": r.description },
+                "defaultConfiguration": { "level": "warning" },
+            })
+        })
+        .collect();
+
+    let results: Vec<serde_json::Value> = records
+        .iter()
+        .map(|r| {
+            json!({
+                "ruleId": r.rule,
+                "ruleIndex": checker::RULES.iter().position(|rule| rule.id == r.rule),
+                "level": level(r.severity),
+                "message": { "text": r.message },
+                "partialFingerprints": { "sassRta/v1": r.fingerprint },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri(&r.file) },
+                        "region": {
+                            "startLine": r.range.from.row + 1,
+                            "startColumn": r.range.from.column + 1,
+                            "endLine": r.range.to.row + 1,
+                            "endColumn": r.range.to.column + 2,
+                        },
+                    },
+                }],
+            })
+        })
+        .collect();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "sass-rta",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&log).unwrap() + "\n"
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Off => "none",
+    }
+}
+
+// Relative paths stay relative references, absolute ones become file URIs.
+fn uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", percent_encode(&path))
+    } else if path.as_bytes().first().is_some_and(u8::is_ascii_alphabetic)
+        && path.as_bytes().get(1) == Some(&b':')
+    {
+        format!("file:///{}{}", &path[..2], percent_encode(&path[2..]))
+    } else {
+        percent_encode(&path)
+    }
+}
+
+// Escapes every byte but the unreserved characters and `/` of RFC 3986.
+fn percent_encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod format {
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        let input = ".a {\n  &-b {}\n}";
+        checker::check(input, &Default::default())
+            .iter()
+            .map(|f| Record::new("a.scss", input, f, Severity::Warning))
+            .collect()
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            super::text(&records()),
+            "a.scss:2:3: evil ampersand `&-b` builds `.a-b`\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let records = records();
+        let fingerprint = records[0].fingerprint.clone();
+        let value: serde_json::Value = serde_json::from_str(&super::json(&records)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([{
+                "file": "a.scss",
+                "rule": "evil-ampersand",
+                "severity": "warning",
+                "message": "evil ampersand `&-b` builds `.a-b`",
+                "range": {
+                    "from": { "row": 1, "column": 2 },
+                    "to": { "row": 1, "column": 4 }
+                },
+                "selector": "&-b",
+                "resolved": [".a-b"],
+                "fixable": true,
+                "policy": null,
+                "fingerprint": fingerprint
+            }])
+        );
+    }
+
+    #[test]
+    fn sarif() {
+        let records = records();
+        let value: serde_json::Value = serde_json::from_str(&super::sarif(&records)).unwrap();
+        assert_eq!(value["version"], "2.1.0");
+        let run = &value["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "sass-rta");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "evil-ampersand");
+        assert_eq!(uri("/a/b.scss"), "file:///a/b.scss");
+        assert_eq!(uri("C:\\a\\b.scss"), "file:///C:/a/b.scss");
+        assert_eq!(uri("a b/#c%.scss"), "a%20b/%23c%25.scss");
+        assert_eq!(uri("/é.scss"), "file:///%C3%A9.scss");
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+        assert_eq!(
+            run["results"],
+            json!([{
+                "ruleId": "evil-ampersand",
+                "ruleIndex": 0,
+                "level": "warning",
+                "message": { "text": "evil ampersand `&-b` builds `.a-b`" },
+                "partialFingerprints": { "sassRta/v1": records[0].fingerprint },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "a.scss" },
+                        "region": {
+                            "startLine": 2,
+                            "startColumn": 3,
+                            "endLine": 2,
+                            "endColumn": 6
+                        }
+                    }
+                }]
+            }])
+        );
+    }
+}
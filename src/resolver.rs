@@ -0,0 +1,184 @@
+use crate::expression::Expr;
+use crate::expression::Scope;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Resolved<'a> {
+    pub scope: &'a Scope,
+    pub parents: Vec<String>,
+    pub selectors: Vec<String>,
+}
+
+// Resolves the selectors of every scope in the tree, in source order.
+pub fn resolve(exprs: &[Expr]) -> Vec<Resolved<'_>> {
+    let mut vec = vec![];
+    walk(exprs, &[], &mut vec);
+    vec
+}
+
+fn walk<'a>(exprs: &'a [Expr], parents: &[String], vec: &mut Vec<Resolved<'a>>) {
+    for expr in exprs {
+        match expr {
+            Expr::Scope(s) if s.is_at_rule() => {
+                // at-rules like `@media` or `@include` keep the parent selectors
+                walk(&s.children, parents, vec);
+            }
+            Expr::Scope(s) => {
+                let selectors = resolve_selectors(parents, &s.selectors);
+                vec.push(Resolved {
+                    scope: s,
+                    parents: parents.to_vec(),
+                    selectors: selectors.clone(),
+                });
+                walk(&s.children, &selectors, vec);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Scope {
+    pub fn is_at_rule(&self) -> bool {
+        self.selectors.iter().any(|s| s.starts_with('@'))
+    }
+}
+
+// Combines every parent with every selector. `&` is replaced by the parent
+// wherever it appears, otherwise the selector becomes a descendant of it.
+pub fn resolve_selectors(parents: &[String], selectors: &[String]) -> Vec<String> {
+    if parents.is_empty() {
+        return selectors.to_vec();
+    }
+
+    let mut resolved = vec![];
+    for parent in parents {
+        for selector in selectors {
+            match substitute(selector, parent) {
+                Some(s) => resolved.push(s),
+                None => resolved.push(format!("{} {}", parent, selector)),
+            }
+        }
+    }
+    resolved
+}
+
+// Replaces `&` outside of quoted strings, or returns `None` without any `&`.
+fn substitute(selector: &str, parent: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaping = false;
+    let mut found = false;
+
+    for c in selector.chars() {
+        match quote {
+            _ if escaping => escaping = false,
+            _ if c == '\\' => escaping = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '&' => {
+                found = true;
+                value.push_str(parent);
+                continue;
+            }
+            None => (),
+        }
+        value.push(c);
+    }
+
+    if found {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod resolve_selectors {
+    use super::*;
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn root() {
+        assert_eq!(
+            resolve_selectors(&[], &strings(&[".a", "&-b"])),
+            strings(&[".a", "&-b"])
+        );
+    }
+
+    #[test]
+    fn descendant() {
+        assert_eq!(
+            resolve_selectors(&strings(&[".a"]), &strings(&[".b", "> .c"])),
+            strings(&[".a .b", ".a > .c"])
+        );
+    }
+
+    #[test]
+    fn amp_position() {
+        assert_eq!(
+            resolve_selectors(
+                &strings(&[".a"]),
+                &strings(&["&__b", "&:hover", ".b &", "x-&", "& + &"])
+            ),
+            strings(&[".a__b", ".a:hover", ".b .a", "x-.a", ".a + .a"])
+        );
+    }
+
+    #[test]
+    fn product() {
+        assert_eq!(
+            resolve_selectors(&strings(&[".a", ".b"]), &strings(&["&-c", ".d"])),
+            strings(&[".a-c", ".a .d", ".b-c", ".b .d"])
+        );
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(
+            resolve_selectors(&strings(&[".a"]), &strings(&["&[title='&']"])),
+            strings(&[".a[title='&']"])
+        );
+        assert_eq!(
+            resolve_selectors(&strings(&[".a"]), &strings(&["[title=\"\\\"&\"]"])),
+            strings(&[".a [title=\"\\\"&\"]"])
+        );
+    }
+}
+
+#[cfg(test)]
+mod resolve {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn do_resolve(input: &str, expect: Vec<Vec<&str>>) {
+        let (exprs, _) = Parser::new(Lexer::new(input.chars().collect())).parse();
+        let selectors: Vec<Vec<String>> =
+            resolve(&exprs).into_iter().map(|r| r.selectors).collect();
+        assert_eq!(selectors, expect);
+    }
+
+    #[test]
+    fn nested() {
+        do_resolve(
+            ".a, .b { &-c { &__d {} .e & {} } color: red; }",
+            vec![
+                vec![".a", ".b"],
+                vec![".a-c", ".b-c"],
+                vec![".a-c__d", ".b-c__d"],
+                vec![".e .a-c", ".e .b-c"],
+            ],
+        );
+    }
+
+    #[test]
+    fn at_rule() {
+        do_resolve(
+            ".a { @media screen { &-b {} } }",
+            vec![vec![".a"], vec![".a-b"]],
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::checker::Finding;
+use crate::checker::UNUSED_SUPPRESSION;
+use crate::expression::Comment;
+use crate::expression::Expr;
+use crate::lexer::Cursor;
+use crate::lexer::Range;
+
+pub const DISABLE_NEXT_LINE: &str = "sass-rta-disable-next-line";
+pub const DISABLE_FILE: &str = "sass-rta-disable-file";
+pub const DISABLE: &str = "sass-rta-disable";
+pub const ENABLE: &str = "sass-rta-enable";
+
+#[derive(Debug, PartialEq, Clone)]
+enum Kind {
+    NextLine(Option<usize>), // row of the next line which is not a comment
+    File,
+    Disable,
+    Enable,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Directive {
+    kind: Kind,
+    rules: Vec<String>, // empty for every rule
+    range: Range,
+    text: String,
+    used: bool,
+}
+
+impl Directive {
+    fn applies(&self, rule: &str) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| r == rule)
+    }
+}
+
+// Directive comments of an input, in source order.
+pub struct Directives(Vec<Directive>);
+
+impl Directives {
+    pub fn new(exprs: &[Expr]) -> Directives {
+        let mut directives = vec![];
+        collect(exprs, &mut directives);
+        directives.sort_by(|a, b| a.range.from.cmp(&b.range.from));
+        Directives(directives)
+    }
+
+    // Whether a finding of `rule` at `at` is disabled.
+    pub fn disable(&self, rule: &str, at: &Cursor) -> bool {
+        self.find(rule, at).is_some()
+    }
+
+    // Index of the directive disabling a finding of `rule` at `at`.
+    fn find(&self, rule: &str, at: &Cursor) -> Option<usize> {
+        let next_line = self.0.iter().position(|d| match &d.kind {
+            Kind::NextLine(Some(row)) => d.applies(rule) && at.row == *row,
+            _ => false,
+        });
+        if next_line.is_some() {
+            return next_line;
+        }
+
+        let mut active = None;
+        for (i, d) in self.0.iter().enumerate() {
+            if &d.range.from > at {
+                break;
+            }
+            match d.kind {
+                Kind::Disable if d.applies(rule) => active = Some(i),
+                Kind::Enable if d.applies(rule) => active = None,
+                _ => (),
+            }
+        }
+        if active.is_some() {
+            return active;
+        }
+
+        self.0
+            .iter()
+            .position(|d| d.kind == Kind::File && d.applies(rule))
+    }
+}
+
+// Drops the findings disabled by directive comments, and reports the
+// disabling directives which did not suppress anything.
+pub fn apply(exprs: &[Expr], findings: Vec<Finding>) -> Vec<Finding> {
+    let mut directives = Directives::new(exprs);
+
+    let mut vec: Vec<Finding> = findings
+        .into_iter()
+        .filter(|f| match directives.find(f.rule, &f.range.from) {
+            Some(i) => {
+                directives.0[i].used = true;
+                false
+            }
+            None => true,
+        })
+        .collect();
+
+    for d in directives.0 {
+        if !d.used && d.kind != Kind::Enable {
+            vec.push(Finding {
+                rule: UNUSED_SUPPRESSION,
+                range: d.range.clone(),
+                scope: d.range,
+                selector: d.text,
+                resolved: vec![],
+                fixable: false,
+                policy: None,
+            });
+        }
+    }
+    vec.sort_by(|a, b| a.range.from.cmp(&b.range.from));
+    vec
+}
+
+fn collect(exprs: &[Expr], directives: &mut Vec<Directive>) {
+    for expr in exprs {
+        match expr {
+            // comments are attached to the expression following them
+            Expr::Scope(s) => {
+                push(&s.comments, Some(s.range.from.row), directives);
+                collect(&s.children, directives);
+            }
+            Expr::Property(p) => push(&p.comments, Some(p.range.from.row), directives),
+            Expr::Import(i) => push(&i.comments, Some(i.range.from.row), directives),
+            Expr::Use(u) => push(&u.comments, Some(u.range.from.row), directives),
+            Expr::Forward(f) => push(&f.comments, Some(f.range.from.row), directives),
+            Expr::Comment(c) => push(std::slice::from_ref(c), None, directives),
+            _ => (),
+        }
+    }
+}
+
+fn push(comments: &[Comment], next: Option<usize>, directives: &mut Vec<Directive>) {
+    for comment in comments {
+        if let Some(mut directive) = parse(comment) {
+            if let Kind::NextLine(_) = directive.kind {
+                directive.kind = Kind::NextLine(next);
+            }
+            directives.push(directive);
+        }
+    }
+}
+
+// Reads `sass-rta-disable rule-a, rule-b -- description` style comments.
+fn parse(comment: &Comment) -> Option<Directive> {
+    let value = comment.value.as_str();
+    let text = if let Some(v) = value.strip_prefix("//") {
+        v
+    } else {
+        value.strip_prefix("/*")?.strip_suffix("*/")?
+    };
+    let text = text.split("--").next().unwrap_or("").trim();
+
+    let mut words = text.split(|c: char| c == ',' || c.is_whitespace());
+    let kind = match words.next()? {
+        DISABLE_NEXT_LINE => Kind::NextLine(None),
+        DISABLE_FILE => Kind::File,
+        DISABLE => Kind::Disable,
+        ENABLE => Kind::Enable,
+        _ => return None,
+    };
+    Some(Directive {
+        kind,
+        rules: words
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect(),
+        range: comment.range.clone(),
+        text: text.to_string(),
+        used: false,
+    })
+}
+
+#[cfg(test)]
+mod apply {
+    use crate::ambuster::Policy;
+    use crate::checker;
+
+    fn rows(input: &str) -> Vec<(usize, &'static str)> {
+        checker::check(input, &Policy::default())
+            .iter()
+            .map(|f| (f.range.from.row, f.rule))
+            .collect()
+    }
+
+    #[test]
+    fn next_line() {
+        assert_eq!(
+            rows(".a {\n  // sass-rta-disable-next-line\n  &-b {\n    &-c {}\n  }\n  &-d {}\n}"),
+            vec![(3, checker::EVIL_AMPERSAND), (5, checker::EVIL_AMPERSAND)]
+        );
+    }
+
+    #[test]
+    fn next_line_multi_line_selectors() {
+        assert_eq!(
+            rows(".a {\n  /* sass-rta-disable-next-line evil-ampersand */\n  &-b,\n  &-c {}\n}"),
+            vec![(3, checker::EVIL_AMPERSAND)]
+        );
+    }
+
+    #[test]
+    fn next_line_skips_comments() {
+        assert_eq!(
+            rows(
+                ".a {\n  // sass-rta-disable-next-line\n  // note\n\n  &-b { &-c {} }\n  &-d {}\n}"
+            ),
+            vec![(5, checker::EVIL_AMPERSAND)]
+        );
+    }
+
+    #[test]
+    fn block() {
+        assert_eq!(
+            rows(
+                ".a {\n  // sass-rta-disable\n  &-b {}\n  &-c {}\n  // sass-rta-enable\n  &-d {}\n}"
+            ),
+            vec![(5, checker::EVIL_AMPERSAND)]
+        );
+    }
+
+    #[test]
+    fn file() {
+        assert_eq!(
+            rows(".a {\n  &-b {}\n}\n// sass-rta-disable-file -- legacy"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn other_rule() {
+        assert_eq!(
+            rows("// sass-rta-disable-file some-rule\n.a { &-b {} }"),
+            vec![
+                (0, checker::UNUSED_SUPPRESSION),
+                (1, checker::EVIL_AMPERSAND)
+            ]
+        );
+    }
+
+    #[test]
+    fn unused() {
+        let findings = checker::check(
+            ".a {\n  // sass-rta-disable-next-line\n  &:hover {}\n}",
+            &Policy::default(),
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, checker::UNUSED_SUPPRESSION);
+        assert_eq!(
+            findings[0].message(),
+            "unused suppression `sass-rta-disable-next-line`"
+        );
+    }
+}
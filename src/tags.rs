@@ -0,0 +1,244 @@
+use crate::checker;
+use crate::finder;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Kind {
+    Class,
+    Id,
+}
+
+impl Kind {
+    fn letter(&self) -> char {
+        match self {
+            Kind::Class => 'c',
+            Kind::Id => 'i',
+        }
+    }
+}
+
+// A class or id added by a rule, without its `.` or `#`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub file: String,
+    pub row: usize,
+    pub kind: Kind,
+    pub line: String,  // text of the row
+    pub offset: usize, // bytes before the row
+}
+
+// Tags of the classes and ids resolved in the file, pointing at the start
+// of the rule which builds them.
+pub fn collect(file: &str, input: &str) -> Vec<Tag> {
+    let chars: Vec<char> = input.chars().collect();
+    let (exprs, _) = Parser::new(Lexer::new(chars.clone())).parse();
+    let tokens = checker::tokens(chars);
+
+    let mut offsets = vec![0];
+    for (i, c) in input.char_indices() {
+        if c == '\n' {
+            offsets.push(i + 1);
+        }
+    }
+    let file = file.strip_prefix("./").unwrap_or(file);
+
+    let mut tags = vec![];
+    for built in finder::built(&exprs, &tokens) {
+        for name in added(&built) {
+            let row = built.scope.range.from.row;
+            let kind = if name.starts_with('#') {
+                Kind::Id
+            } else {
+                Kind::Class
+            };
+            tags.push(Tag {
+                name: name[1..].to_string(),
+                file: file.to_string(),
+                row,
+                kind,
+                line: input.lines().nth(row).unwrap_or("").to_string(),
+                offset: offsets.get(row).cloned().unwrap_or(0),
+            });
+        }
+    }
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+// Classes and ids which the selector adds to the ones of its parent.
+pub fn added(built: &finder::Built) -> Vec<String> {
+    let mut inherited = names(&built.parent);
+    let mut vec = vec![];
+    for name in names(&built.resolved) {
+        match inherited.iter().position(|n| n == &name) {
+            Some(i) => {
+                inherited.remove(i);
+            }
+            None => vec.push(name),
+        }
+    }
+    vec
+}
+
+// Classes and ids of a selector with their `.` or `#`, except the ones
+// in attribute selectors, strings and interpolations.
+pub fn names(selector: &str) -> Vec<String> {
+    let chars: Vec<char> = selector.chars().collect();
+    let mut names = vec![];
+    let mut quote: Option<char> = None;
+    let mut brackets = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match quote {
+            Some(_) if c == '\\' => i += 1,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None => match c {
+                '\\' => i += 1,
+                '"' | '\'' => quote = Some(c),
+                '[' => brackets += 1,
+                ']' => brackets -= 1,
+                '.' | '#' if brackets == 0 => {
+                    let name: String = chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_'))
+                        .collect();
+                    if name.starts_with(|c: char| !c.is_ascii_digit()) {
+                        i += name.chars().count();
+                        names.push(format!("{}{}", c, name));
+                    }
+                }
+                _ => (),
+            },
+        }
+        i += 1;
+    }
+    names
+}
+
+// Universal-ctags file sorted by tag name, with line number addresses.
+pub fn ctags(tags: &[Tag]) -> String {
+    let mut tags = tags.to_vec();
+    tags.sort_by(|a, b| (&a.name, &a.file, a.row).cmp(&(&b.name, &b.file, b.row)));
+    tags.dedup_by(|a, b| (&a.name, &a.file, a.row) == (&b.name, &b.file, b.row));
+
+    let mut output = [
+        "!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;\" to lines/",
+        "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/",
+        "!_TAG_KIND_DESCRIPTION!SCSS\tc,class\t/classes/",
+        "!_TAG_KIND_DESCRIPTION!SCSS\ti,id\t/identifiers/",
+        "!_TAG_PROGRAM_NAME\tsass-rta\t//",
+    ]
+    .iter()
+    .map(|l| format!("{}\n", l))
+    .collect::<String>();
+    output.push_str(&format!(
+        "!_TAG_PROGRAM_VERSION\t{}\t//\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    for t in &tags {
+        output.push_str(&format!(
+            "{}\t{}\t{};\"\t{}\tline:{}\n",
+            t.name,
+            t.file,
+            t.row + 1,
+            t.kind.letter(),
+            t.row + 1
+        ));
+    }
+    output
+}
+
+// Emacs TAGS file with a section per file in the given order.
+pub fn etags(tags: &[Tag]) -> String {
+    let mut files: Vec<&str> = vec![];
+    for t in tags {
+        if !files.contains(&t.file.as_str()) {
+            files.push(&t.file);
+        }
+    }
+
+    let mut output = String::new();
+    for file in files {
+        let mut section = String::new();
+        let mut tags: Vec<&Tag> = tags.iter().filter(|t| t.file == file).collect();
+        tags.sort_by_key(|t| (t.row, &t.name));
+        for t in tags {
+            section.push_str(&format!(
+                "{}\x7f{}\x01{},{}\n",
+                t.line.trim_end(),
+                t.name,
+                t.row + 1,
+                t.offset
+            ));
+        }
+        output.push_str(&format!("\x0c\n{},{}\n{}", file, section.len(), section));
+    }
+    output
+}
+
+#[cfg(test)]
+mod format {
+    use super::*;
+
+    const INPUT: &str = ".card {\n  &__title, #main &-x {\n    &--active:hover {}\n  }\n}\n";
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            super::names(".a.b-c > #d a[href$=\".pdf\"]:not(.e) .1x #{$f}"),
+            vec![".a", ".b-c", "#d", ".e"]
+        );
+    }
+
+    #[test]
+    fn collect() {
+        let tags: Vec<(String, usize, Kind)> = super::collect("./a.scss", INPUT)
+            .into_iter()
+            .map(|t| (t.name, t.row, t.kind))
+            .collect();
+        assert_eq!(
+            tags,
+            vec![
+                ("card".to_string(), 0, Kind::Class),
+                ("card-x".to_string(), 1, Kind::Class),
+                ("card-x--active".to_string(), 2, Kind::Class),
+                ("card__title".to_string(), 1, Kind::Class),
+                ("card__title--active".to_string(), 2, Kind::Class),
+                ("main".to_string(), 1, Kind::Id),
+            ]
+        );
+    }
+
+    #[test]
+    fn ctags() {
+        let output = super::ctags(&super::collect("a.scss", INPUT));
+        let lines: Vec<&str> = output.lines().filter(|l| !l.starts_with('!')).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "card\ta.scss\t1;\"\tc\tline:1",
+                "card-x\ta.scss\t2;\"\tc\tline:2",
+                "card-x--active\ta.scss\t3;\"\tc\tline:3",
+                "card__title\ta.scss\t2;\"\tc\tline:2",
+                "card__title--active\ta.scss\t3;\"\tc\tline:3",
+                "main\ta.scss\t2;\"\ti\tline:2",
+            ]
+        );
+        assert!(output.starts_with("!_TAG_FILE_FORMAT\t2\t"));
+    }
+
+    #[test]
+    fn etags() {
+        let output = super::etags(&super::collect("a.scss", ".a {\n  &-b {}\n}\n"));
+        let section = ".a {\x7fa\x011,0\n  &-b {}\x7fa-b\x012,5\n";
+        assert_eq!(
+            output,
+            format!("\x0c\na.scss,{}\n{}", section.len(), section)
+        );
+    }
+}
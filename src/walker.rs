@@ -0,0 +1,449 @@
+use globset::Glob;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+use ignore::WalkBuilder;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+pub const DEFAULT_INCLUDE: &str = "*.scss";
+
+pub const IGNORE_FILE: &str = ".sass-rtaignore";
+
+const SKIPPED_DIRS: &[&str] = &["node_modules"];
+
+// Glob patterns matching paths relative to `base`, or to the walked
+// directory without it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Patterns {
+    pub base: Option<PathBuf>,
+    pub globs: Vec<String>,
+}
+
+impl Patterns {
+    pub fn new(base: Option<&Path>, globs: Vec<String>) -> Patterns {
+        Patterns {
+            base: base.map(Path::to_path_buf),
+            globs,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Options {
+    pub include: Patterns,
+    pub exclude: Patterns,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            include: Patterns::new(None, vec![DEFAULT_INCLUDE.to_string()]),
+            exclude: Patterns::new(None, vec![]),
+        }
+    }
+}
+
+// Gives the patterns to apply to a file or directory.
+pub type OptionsFor = Arc<dyn Fn(&Path) -> Result<Options, String> + Send + Sync>;
+
+struct Matcher {
+    options: Options,
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+// Compiled patterns, shared by the files with the same options.
+#[derive(Default)]
+struct Matchers(Vec<Matcher>);
+
+impl Matchers {
+    fn get(&mut self, options: Options) -> Result<&Matcher, String> {
+        let i = match self.0.iter().position(|m| m.options == options) {
+            Some(i) => i,
+            None => {
+                self.0.push(Matcher {
+                    include: glob_set(&options.include.globs)?,
+                    exclude: glob_set(&options.exclude.globs)?,
+                    options,
+                });
+                self.0.len() - 1
+            }
+        };
+        Ok(&self.0[i])
+    }
+}
+
+// Expands targets into the files to process. A target is a file, a
+// directory walked recursively, or a glob pattern such as `src/**/*.scss`.
+// Walking honours `.gitignore`, `.git/info/exclude` and `.sass-rtaignore`,
+// and `options_for` gives the patterns to apply to each found file and
+// directory. Files given explicitly are kept even if they do not match
+// `include` or are ignored. Files found by several targets are listed once.
+pub fn files(targets: &[&str], options_for: OptionsFor) -> Result<Vec<PathBuf>, String> {
+    let matchers = Arc::new(Mutex::new(Matchers::default()));
+    let mut files = vec![];
+    for target in targets {
+        let (base, pattern) = if is_glob(target) {
+            let pattern = Glob::new(target)
+                .map_err(|e| e.to_string())?
+                .compile_matcher();
+            (glob_base(target), Some(pattern))
+        } else {
+            (PathBuf::from(target), None)
+        };
+
+        if base.is_file() && pattern.is_none() {
+            files.push(base);
+            continue;
+        }
+        if !base.exists() {
+            return Err(format!("{}: No such file or directory", target));
+        }
+
+        let walk = walk_builder(&base, options_for.clone(), matchers.clone()).build();
+        for entry in walk {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+
+            let mut matchers = matchers.lock().unwrap();
+            let matcher = matchers.get(options_for(path)?)?;
+            if excluded(&matcher.exclude, &matcher.options.exclude, &base, path)? {
+                continue;
+            }
+            let selected = match &pattern {
+                Some(p) => p.is_match(path.strip_prefix("./").unwrap_or(path)),
+                None => included(&matcher.include, &matcher.options.include, &base, path)?,
+            };
+            if selected {
+                files.push(path.to_path_buf());
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|f| seen.insert(fs::canonicalize(f).unwrap_or_else(|_| f.clone())));
+    Ok(files)
+}
+
+// Keeps the paths matching their include and exclude patterns, taking
+// patterns without a base as relative to the current directory.
+pub fn filter(
+    paths: Vec<PathBuf>,
+    options_for: &dyn Fn(&Path) -> Result<Options, String>,
+) -> Result<Vec<PathBuf>, String> {
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    let mut matchers = Matchers::default();
+    let mut files = vec![];
+    for path in paths {
+        let matcher = matchers.get(options_for(&path)?)?;
+        if !excluded(&matcher.exclude, &matcher.options.exclude, &cwd, &path)?
+            && included(&matcher.include, &matcher.options.include, &cwd, &path)?
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+// Excluded directories are not walked at all. Their errors are left to the
+// files in them.
+fn walk_builder(
+    base: &Path,
+    options_for: OptionsFor,
+    matchers: Arc<Mutex<Matchers>>,
+) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(base);
+    let walked = base.to_path_buf();
+    builder
+        .hidden(true)
+        .parents(true)
+        .ignore(false)
+        .git_ignore(true)
+        .git_global(false)
+        .git_exclude(true)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            if entry.depth() == 0 || !entry.file_type().is_some_and(|t| t.is_dir()) {
+                return true;
+            }
+            let name = entry.file_name().to_string_lossy();
+            if SKIPPED_DIRS.contains(&name.as_ref()) {
+                return false;
+            }
+            let path = entry.path();
+            let mut matchers = matchers.lock().unwrap();
+            let excluded = options_for(path)
+                .and_then(|options| matchers.get(options))
+                .and_then(|m| excluded(&m.exclude, &m.options.exclude, &walked, path));
+            !excluded.unwrap_or(false)
+        });
+    builder
+}
+
+// Patterns match either the relative path or the file name, so that
+// `*.scss` and `legacy/**` both work as expected.
+fn included(
+    set: &GlobSet,
+    patterns: &Patterns,
+    walked: &Path,
+    path: &Path,
+) -> Result<bool, String> {
+    let (base, path) = relative_to(patterns, walked, path)?;
+    let relative = path.strip_prefix(&base).unwrap_or(&path);
+    Ok(set.is_match(relative) || path.file_name().is_some_and(|name| set.is_match(name)))
+}
+
+// Excluding a directory excludes everything in it.
+fn excluded(
+    set: &GlobSet,
+    patterns: &Patterns,
+    walked: &Path,
+    path: &Path,
+) -> Result<bool, String> {
+    if patterns.globs.is_empty() {
+        return Ok(false);
+    }
+    let (base, path) = relative_to(patterns, walked, path)?;
+    for ancestor in path.ancestors() {
+        let relative = match ancestor.strip_prefix(&base) {
+            Ok(relative) if relative.as_os_str().is_empty() => break,
+            Ok(relative) => relative,
+            Err(_) => break,
+        };
+        if set.is_match(relative) || ancestor.file_name().is_some_and(|name| set.is_match(name)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Config patterns have an absolute base, so the path becomes absolute too.
+fn relative_to(
+    patterns: &Patterns,
+    walked: &Path,
+    path: &Path,
+) -> Result<(PathBuf, PathBuf), String> {
+    match &patterns.base {
+        Some(base) => {
+            let path = std::path::absolute(path).map_err(|e| e.to_string())?;
+            Ok((base.clone(), path))
+        }
+        None => Ok((walked.to_path_buf(), path.to_path_buf())),
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn is_glob(target: &str) -> bool {
+    target.contains(['*', '?', '[', '{'])
+}
+
+// The longest leading directory of the pattern without glob characters.
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if is_glob(&component) {
+            break;
+        }
+        base.push(component.as_ref());
+    }
+    if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    }
+}
+
+#[cfg(test)]
+mod files {
+    use super::*;
+    use std::fs;
+
+    fn setup(paths: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for path in paths {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    fn relative(dir: &tempfile::TempDir, files: Vec<PathBuf>) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| {
+                f.strip_prefix(dir.path())
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn directory() {
+        let dir = setup(&[
+            "a.scss",
+            "b.css",
+            "c/d.scss",
+            "node_modules/e.scss",
+            ".cache/f.scss",
+        ]);
+        let root = dir.path().to_string_lossy().to_string();
+        let files = files(&[&root], Arc::new(|_: &Path| Ok(Options::default()))).unwrap();
+        assert_eq!(relative(&dir, files), vec!["a.scss", "c/d.scss"]);
+    }
+
+    #[test]
+    fn include_exclude() {
+        let dir = setup(&[
+            "a.scss",
+            "b.sass",
+            "c/d.scss",
+            "legacy/e.sass",
+            "f.gen.scss",
+        ]);
+        let root = dir.path().to_string_lossy().to_string();
+        let options = Options {
+            include: Patterns::new(None, vec!["*.scss".to_string(), "*.sass".to_string()]),
+            exclude: Patterns::new(None, vec!["legacy".to_string(), "*.gen.scss".to_string()]),
+        };
+        let files = files(&[&root], Arc::new(move |_: &Path| Ok(options.clone()))).unwrap();
+        assert_eq!(relative(&dir, files), vec!["a.scss", "b.sass", "c/d.scss"]);
+    }
+
+    #[test]
+    fn glob_and_file() {
+        let dir = setup(&["a.scss", "c/d.scss", "c/e/f.scss", "g.css"]);
+        let pattern = format!("{}/c/**/*.scss", dir.path().to_string_lossy());
+        let file = format!("{}/g.css", dir.path().to_string_lossy());
+        let files = files(
+            &[&pattern, &file],
+            Arc::new(|_: &Path| Ok(Options::default())),
+        )
+        .unwrap();
+        assert_eq!(
+            relative(&dir, files),
+            vec!["c/d.scss", "c/e/f.scss", "g.css"]
+        );
+    }
+
+    #[test]
+    fn overlapping() {
+        let dir = setup(&["a.scss", "c/d.scss"]);
+        let root = dir.path().to_string_lossy().to_string();
+        let file = format!("{}/c/../a.scss", root);
+        let pattern = format!("{}/**/*.scss", root);
+        let files = files(
+            &[&root, &file, &pattern],
+            Arc::new(|_: &Path| Ok(Options::default())),
+        )
+        .unwrap();
+        assert_eq!(relative(&dir, files), vec!["a.scss", "c/d.scss"]);
+    }
+
+    #[test]
+    fn missing() {
+        assert!(files(
+            &["/no/such/dir"],
+            Arc::new(|_: &Path| Ok(Options::default()))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ignore_files() {
+        let dir = setup(&[
+            "a.scss",
+            "generated/b.scss",
+            "generated/keep.scss",
+            "tokens.scss",
+            "c/tokens.scss",
+            "c/d.scss",
+            "e.scss",
+        ]);
+        fs::write(dir.path().join(".gitignore"), "generated/*\n!keep.scss\n").unwrap();
+        fs::write(dir.path().join(IGNORE_FILE), "/tokens.scss\n").unwrap();
+        fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        fs::write(dir.path().join(".git/info/exclude"), "e.scss\n").unwrap();
+        fs::write(dir.path().join("c/.gitignore"), "d.scss\n").unwrap();
+
+        let root = dir.path().to_string_lossy().to_string();
+        let files = files(&[&root], Arc::new(|_: &Path| Ok(Options::default()))).unwrap();
+        assert_eq!(
+            relative(&dir, files),
+            vec!["a.scss", "c/tokens.scss", "generated/keep.scss"]
+        );
+    }
+
+    #[test]
+    fn options_per_file() {
+        let dir = setup(&["a.scss", "pkg/b.scss", "pkg/c.sass", "pkg/legacy/d.sass"]);
+        let root = dir.path().to_string_lossy().to_string();
+        let pkg = dir.path().join("pkg");
+        let options_for = move |path: &Path| {
+            if path.starts_with(&pkg) {
+                Ok(Options {
+                    include: Patterns::new(Some(&pkg), vec!["*.sass".to_string()]),
+                    exclude: Patterns::new(Some(&pkg), vec!["legacy/**".to_string()]),
+                })
+            } else {
+                Ok(Options::default())
+            }
+        };
+        let files = files(&[&root], Arc::new(options_for)).unwrap();
+        assert_eq!(relative(&dir, files), vec!["a.scss", "pkg/c.sass"]);
+    }
+
+    #[test]
+    fn excluded_dirs() {
+        let dir = setup(&["a.scss", "legacy/b.scss", "legacy/c/d.scss"]);
+        let root = dir.path().to_string_lossy().to_string();
+        let legacy = dir.path().join("legacy");
+        // the files of an excluded directory are never looked at
+        let options_for = move |path: &Path| {
+            if path.starts_with(&legacy) && path.is_file() {
+                return Err(format!("{}: looked at", path.display()));
+            }
+            Ok(Options {
+                include: Patterns::new(None, vec!["*.scss".to_string()]),
+                exclude: Patterns::new(None, vec!["legacy".to_string()]),
+            })
+        };
+        let files = files(&[&root], Arc::new(options_for)).unwrap();
+        assert_eq!(relative(&dir, files), vec!["a.scss"]);
+    }
+
+    #[test]
+    fn filter() {
+        let dir = setup(&["a.scss", "b.css", "legacy/c.scss"]);
+        let paths = ["a.scss", "b.css", "legacy/c.scss"]
+            .iter()
+            .map(|p| dir.path().join(p))
+            .collect();
+        let options = Options {
+            include: Patterns::new(None, vec!["*.scss".to_string()]),
+            exclude: Patterns::new(Some(dir.path()), vec!["legacy".to_string()]),
+        };
+        let files = super::filter(paths, &|_| Ok(options.clone())).unwrap();
+        assert_eq!(relative(&dir, files), vec!["a.scss"]);
+    }
+}